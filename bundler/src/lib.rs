@@ -0,0 +1,191 @@
+//! Build script helper that turns a directory of web files into a `StaticAssets` table for `embedded_web`.
+//!
+//! In `build.rs`:
+//!
+//! ```ignore
+//! fn main() {
+//!     embedded_web_bundler::bundle("web", std::env::var("OUT_DIR").unwrap()).unwrap();
+//! }
+//! ```
+//!
+//! And in the firmware:
+//!
+//! ```ignore
+//! static ASSETS: embedded_web::StaticAssets = include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+//! ```
+//!
+//! Every file is served at its path relative to the directory, `index.html` files are also served at the path of
+//! their directory. Files that get smaller when compressed get a gzip variant, and every file gets an `ETag` based on
+//! a hash of its content. The gzip variant gets its own `ETag`, from a hash of the compressed content.
+
+use flate2::{write::GzEncoder, Compression};
+use std::fmt::Write as _;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+/// Bundle all files in `web_dir` into `out_dir/assets.rs`. The gzip compressed files are written to `out_dir/assets/`.
+///
+/// This also tells cargo to rerun the build script when something in `web_dir` changes.
+pub fn bundle(web_dir: impl AsRef<Path>, out_dir: impl AsRef<Path>) -> io::Result<()> {
+    let web_dir = web_dir.as_ref();
+    let out_dir = out_dir.as_ref();
+    println!("cargo:rerun-if-changed={}", web_dir.display());
+
+    let mut files = Vec::new();
+    collect_files(web_dir, &mut files)?;
+    // sort so the generated code does not depend on the order of the file system
+    files.sort();
+
+    let gzip_dir = out_dir.join("assets");
+    std::fs::create_dir_all(&gzip_dir)?;
+
+    let mut code = String::from("embedded_web::StaticAssets::new(&[\n");
+    for (index, file) in files.iter().enumerate() {
+        let bytes = std::fs::read(file)?;
+        let relative = file.strip_prefix(web_dir).unwrap_or(file);
+        let path = url_path(relative);
+
+        let gzip = gzip(&bytes)?;
+        let gzip_file = if gzip.len() < bytes.len() {
+            let gzip_file = gzip_dir.join(format!("{index}.gz"));
+            std::fs::write(&gzip_file, &gzip)?;
+            Some((gzip_file, etag(&gzip)))
+        } else {
+            None
+        };
+
+        let file_path = absolute(file)?;
+        let gzip_file = match gzip_file {
+            Some((gzip_file, gzip_etag)) => Some((absolute(&gzip_file)?, gzip_etag)),
+            None => None,
+        };
+        let content_type = content_type(file);
+        let etag = etag(&bytes);
+        let mut write_asset = |path: &str| {
+            let _ = write!(
+                code,
+                "    embedded_web::Asset::new({path:?}, {content_type:?}, include_bytes!({file_path:?}))\
+                 .with_etag({etag:?})"
+            );
+            if let Some((gzip_file, gzip_etag)) = &gzip_file {
+                let _ = write!(
+                    code,
+                    ".with_gzip(include_bytes!({gzip_file:?})).with_gzip_etag({gzip_etag:?})"
+                );
+            }
+            code.push_str(",\n");
+        };
+
+        write_asset(&path);
+        // `/dir/index.html` is also served as `/dir/`
+        let is_index = relative.file_name() == Some("index.html".as_ref());
+        if let Some(directory) = path.strip_suffix("index.html").filter(|_| is_index) {
+            write_asset(directory);
+        }
+    }
+    code.push_str("])\n");
+
+    std::fs::File::create(out_dir.join("assets.rs"))?.write_all(code.as_bytes())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// `include_bytes!` is relative to the file that includes it, so all paths in the generated code are absolute.
+fn absolute(path: &Path) -> io::Result<String> {
+    Ok(std::fs::canonicalize(path)?.display().to_string())
+}
+
+/// The path the file is served at, e.g. `css/style.css` becomes `/css/style.css`.
+fn url_path(relative: &Path) -> String {
+    relative
+        .components()
+        .fold(String::new(), |path, component| {
+            path + "/" + &component.as_os_str().to_string_lossy()
+        })
+}
+
+fn gzip(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+/// A strong `ETag` from the FNV-1a hash of the content, including the quotes.
+fn etag(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("\"{hash:016x}\"")
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("wasm") => "application/wasm",
+        Some("woff2") => "font/woff2",
+        Some("woff") => "font/woff",
+        _ => "application/octet-stream",
+    }
+}
+
+#[test]
+fn test_bundle() {
+    let dir = std::env::temp_dir().join(format!("embedded_web_bundler_{}", std::process::id()));
+    let web_dir = dir.join("web");
+    std::fs::create_dir_all(web_dir.join("css")).unwrap();
+    std::fs::write(web_dir.join("index.html"), "<p>hi</p>".repeat(20)).unwrap();
+    std::fs::write(web_dir.join("css/style.css"), "p{}").unwrap();
+    std::fs::write(web_dir.join("my-index.html"), "").unwrap();
+
+    bundle(&web_dir, &dir).unwrap();
+    let code = std::fs::read_to_string(dir.join("assets.rs")).unwrap();
+    let lines: Vec<&str> = code.lines().collect();
+    assert_eq!(lines.len(), 6);
+    // too small to compress
+    assert!(lines[1].starts_with(r#"    embedded_web::Asset::new("/css/style.css", "text/css; charset=utf-8", include_bytes!("#));
+    assert!(!lines[1].contains("with_gzip"));
+    assert!(lines[2]
+        .starts_with(r#"    embedded_web::Asset::new("/index.html", "text/html; charset=utf-8""#));
+    assert!(lines[2].contains(".with_gzip(include_bytes!("));
+    // the variants have different `ETag`s
+    let argument = |call: &str| {
+        lines[2]
+            .split(call)
+            .nth(1)
+            .unwrap()
+            .split(')')
+            .next()
+            .unwrap()
+    };
+    assert_ne!(argument(".with_etag("), argument(".with_gzip_etag("));
+    assert!(lines[3].starts_with(r#"    embedded_web::Asset::new("/", "#));
+    assert!(lines[3].ends_with(&lines[2][lines[2].find(',').unwrap()..]));
+    // only `index.html` itself is also served at its directory
+    assert!(lines[4].starts_with(r#"    embedded_web::Asset::new("/my-index.html", "#));
+    assert_eq!(etag(b""), "\"cbf29ce484222325\"");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
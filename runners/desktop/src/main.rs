@@ -2,10 +2,11 @@ use std::{
     collections::HashMap,
     io::{Read, Write},
     net::{TcpListener, TcpStream},
-    sync::mpsc::{channel, Sender},
+    sync::mpsc::{channel, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
 };
 
-use embedded_web::{Error, HttpMethod, SocketAddr, WriteResult};
+use embedded_web::{Error, HttpMethod, HttpVersion, SocketAddr, WriteResult};
 
 fn main() {
     let mut server: embedded_web::Server<_, 4> = embedded_web::Server::new(ServerImpl::default());
@@ -13,20 +14,34 @@ fn main() {
     let (sender, receiver) = channel::<ToMainLoop>();
 
     spawn_listener(sender.clone());
-    while let Ok(msg) = receiver.recv() {
+    let start = Instant::now();
+    loop {
+        // wake up regularly, so the server can close connections that timed out
+        let msg = match receiver.recv_timeout(Duration::from_secs(1)) {
+            Ok(msg) => Some(msg),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        for addr in server.tick(start.elapsed().as_millis() as u64) {
+            println!("Connection {addr:?} timed out");
+        }
+        let Some(msg) = msg else {
+            continue;
+        };
         match msg {
             ToMainLoop::NewClient(stream, addr) => {
+                // insert the stream first, so the server can send a response if it rejects the connection
+                server.inner.clients.insert(
+                    addr,
+                    stream.try_clone().expect("Could not clone TCP stream"),
+                );
                 if let Err(e) = server.new_connection(addr) {
                     println!("Could not accept connection from {addr:?}: {e:?}");
+                    server.inner.clients.remove(&addr);
                     continue;
                 }
                 println!("New connection from {addr:?}");
-                spawn_client(
-                    stream.try_clone().expect("Could not clone TCP stream"),
-                    addr,
-                    sender.clone(),
-                );
-                server.inner.clients.insert(addr, stream);
+                spawn_client(stream, addr, sender.clone());
             }
             ToMainLoop::ClientDisconnected(addr) => {
                 println!("Client {addr:?} disconnected");
@@ -40,8 +55,14 @@ fn main() {
                     server.inner.clients.remove(&addr);
                     continue;
                 }
-                if let Err(e) = server.poll_write() {
-                    eprintln!("Could not poll write after data received: {e:?}");
+                loop {
+                    if let Err(e) = server.poll_write() {
+                        eprintln!("Could not poll write after data received: {e:?}");
+                        break;
+                    }
+                    if !server.is_writing() {
+                        break;
+                    }
                 }
             }
         }
@@ -97,20 +118,28 @@ struct ServerImpl {
 impl embedded_web::ServerConfig for ServerImpl {
     type ConnectionType = ConnectionImpl;
 
+    // browsers keep connections open for a while, don't let them block new clients
+    const EVICTION: embedded_web::EvictionPolicy = embedded_web::EvictionPolicy::OldestIdle;
+
     fn new_connection(
         &self,
         addr: embedded_web::SocketAddr,
         method: embedded_web::HttpMethod,
         path: &str,
+        version: embedded_web::HttpVersion,
     ) -> embedded_web::Result<Self::ConnectionType> {
-        Ok(ConnectionImpl::new(addr, method, path))
+        Ok(ConnectionImpl::new(addr, method, path, version))
     }
 
     fn on_client_done_writing(
         &mut self,
         addr: std::net::SocketAddr,
-        _client: &mut Self::ConnectionType,
+        client: &mut Self::ConnectionType,
     ) {
+        println!("Done writing {:?} to {addr:?}", client.path);
+    }
+
+    fn close_connection(&mut self, addr: embedded_web::SocketAddr) {
         if let Err(e) = self.clients[&addr].shutdown(std::net::Shutdown::Both) {
             eprintln!("Could not shut down stream for {addr:?}: {e:?}");
         }
@@ -136,15 +165,22 @@ impl embedded_web::ServerConfig for ServerImpl {
 struct ConnectionImpl {
     addr: SocketAddr,
     method: HttpMethod,
+    version: HttpVersion,
     path: String,
     headers: Vec<(String, String)>,
 }
 
 impl ConnectionImpl {
-    fn new(addr: SocketAddr, method: HttpMethod, path: &str) -> ConnectionImpl {
+    fn new(
+        addr: SocketAddr,
+        method: HttpMethod,
+        path: &str,
+        version: HttpVersion,
+    ) -> ConnectionImpl {
         Self {
             addr,
             method,
+            version,
             path: path.to_owned(),
             headers: Vec::new(),
         }
@@ -158,11 +194,7 @@ impl embedded_web::Connection for ConnectionImpl {
     }
 
     fn switch_from_headers_to_body(&mut self) -> embedded_web::ReadResult {
-        if self.method == HttpMethod::Get {
-            embedded_web::ReadResult::EndReading
-        } else {
-            embedded_web::ReadResult::Ok
-        }
+        embedded_web::ReadResult::Ok
     }
 
     fn body_received(&mut self, bytes: &[u8]) -> embedded_web::ReadResult {
@@ -176,11 +208,19 @@ impl embedded_web::Connection for ConnectionImpl {
     ) -> embedded_web::WriteResult {
         writer.write_status(200, "OK")?;
         writer.write_response_header("content-type", "text/plain")?;
-        writer.end_headers_start_body()?;
+        writer.end_headers_start_chunked_body()?;
         writeln!(writer, "Hello world from embedded web server")?;
         writeln!(writer)?;
         writeln!(writer, "Method: {:?}", self.method)?;
+        writeln!(writer, "Version: {:?}", self.version)?;
         writeln!(writer, "Requested path: {:?}", self.path)?;
+        if let Ok(uri) = embedded_web::Uri::parse(&self.path) {
+            let mut buffer = [0u8; 256];
+            if let Ok(path) = uri.decoded_path().decode_into(&mut buffer) {
+                writeln!(writer, "Decoded path: {path:?}")?;
+            }
+            writeln!(writer, "Query: {:?}", uri.query())?;
+        }
         writeln!(writer)?;
         writeln!(writer, "Received headers:")?;
         for (k, v) in &self.headers {
@@ -0,0 +1,325 @@
+use crate::{
+    conditional::etag_matches, http_utilities::header_parameters, range::CLOSE_DELIMITER_LENGTH,
+    Connection, HeaderName, HttpMethod, RangeRequest, RangeResponse, ReadResult, ResponseWriter,
+    WriteResult,
+};
+
+/// A file that is embedded in the binary, e.g. with `include_bytes!`.
+pub struct Asset {
+    pub path: &'static str,
+    pub content_type: &'static str,
+    pub bytes: &'static [u8],
+    /// The gzip compressed `bytes`, sent with `Content-Encoding: gzip` to clients that accept it
+    pub gzip: Option<&'static [u8]>,
+    /// The `ETag` header that is sent with the asset, including the quotes
+    pub etag: Option<&'static str>,
+    /// The `ETag` of the gzip variant. This has to differ from `etag`, caches tell the variants apart by it.
+    pub gzip_etag: Option<&'static str>,
+}
+
+impl Asset {
+    pub const fn new(path: &'static str, content_type: &'static str, bytes: &'static [u8]) -> Self {
+        Self {
+            path,
+            content_type,
+            bytes,
+            gzip: None,
+            etag: None,
+            gzip_etag: None,
+        }
+    }
+
+    pub const fn with_gzip(mut self, gzip: &'static [u8]) -> Self {
+        self.gzip = Some(gzip);
+        self
+    }
+
+    pub const fn with_etag(mut self, etag: &'static str) -> Self {
+        self.etag = Some(etag);
+        self
+    }
+
+    pub const fn with_gzip_etag(mut self, etag: &'static str) -> Self {
+        self.gzip_etag = Some(etag);
+        self
+    }
+}
+
+/// A const table of `Asset`s that can be served with an `AssetConnection`.
+///
+/// ```ignore
+/// static ASSETS: StaticAssets = StaticAssets::new(&[
+///     Asset::new("/", "text/html", include_bytes!("../web/index.html")),
+///     Asset::new("/app.js", "text/javascript", include_bytes!("../web/app.js")),
+/// ]);
+/// ```
+///
+/// The table can also be generated at build time with the `embedded_web_bundler` crate.
+pub struct StaticAssets {
+    assets: &'static [Asset],
+}
+
+impl StaticAssets {
+    pub const fn new(assets: &'static [Asset]) -> Self {
+        Self { assets }
+    }
+
+    /// Find the asset for `path`. Anything after a `?` in the path is ignored.
+    pub fn get(&self, path: &str) -> Option<&'static Asset> {
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        self.assets.iter().find(|asset| asset.path == path)
+    }
+
+    /// Create a connection that serves the asset for `path`, or `404 Not Found` if there is no such asset.
+    pub fn connection<const SLICE_SIZE: usize>(
+        &self,
+        method: HttpMethod,
+        path: &str,
+    ) -> AssetConnection<SLICE_SIZE> {
+        AssetConnection::new(method, self.get(path))
+    }
+}
+
+/// A `Connection` that serves a single `Asset`.
+///
+/// The asset is written in slices of at most `SLICE_SIZE` bytes, one slice for every `Connection::poll_write` call.
+/// `Range` requests are supported, with `If-Range` for assets that have an `ETag`.
+pub struct AssetConnection<const SLICE_SIZE: usize = 1024> {
+    method: HttpMethod,
+    asset: Option<&'static Asset>,
+    /// The part of the asset that is written, or `None` if the headers have not been written yet
+    response: Option<RangeResponse>,
+    range: RangeRequest,
+    /// `true` if the client sent an `Accept-Encoding` header that allows gzip
+    accepts_gzip: bool,
+    /// The conditional headers for the identity and gzip variant, which have different `ETag`s
+    identity: Conditions,
+    gzip: Conditions,
+}
+
+/// The result of the conditional headers of a request for one variant of an asset.
+#[derive(Default, Copy, Clone)]
+struct Conditions {
+    /// `true` if the `If-None-Match` header matches the `ETag` of the variant
+    not_modified: bool,
+    /// `true` if the `If-Range` header does not match the variant, so the `Range` header has to be ignored
+    range_outdated: bool,
+}
+
+impl Conditions {
+    fn header_received(&mut self, name: HeaderName, value: &str, etag: Option<&str>) {
+        match name {
+            HeaderName::IfNoneMatch => {
+                // The asset is already known, so there is no need to store the header like `ConditionalHeaders` does
+                self.not_modified |= etag.is_some_and(|etag| etag_matches(value, etag));
+            }
+            HeaderName::IfRange => {
+                // If-Range uses the strong comparison, and a date never matches because assets have no
+                // `Last-Modified`
+                self.range_outdated = value.starts_with("W/") || etag != Some(value);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<const SLICE_SIZE: usize> AssetConnection<SLICE_SIZE> {
+    /// Serve `asset`, or `404 Not Found` if `asset` is `None`.
+    pub fn new(method: HttpMethod, asset: Option<&'static Asset>) -> Self {
+        Self {
+            method,
+            asset,
+            response: None,
+            range: RangeRequest::new(),
+            accepts_gzip: false,
+            identity: Conditions::default(),
+            gzip: Conditions::default(),
+        }
+    }
+
+    /// The bytes, `ETag` and conditions of the variant that is sent for `asset`, this is the gzip variant if the
+    /// client accepts it.
+    fn variant(&self, asset: &'static Asset) -> (&'static [u8], Option<&'static str>, Conditions) {
+        match asset.gzip {
+            Some(gzip) if self.accepts_gzip => (gzip, asset.gzip_etag, self.gzip),
+            _ => (asset.bytes, asset.etag, self.identity),
+        }
+    }
+}
+
+/// Returns `true` if an `Accept-Encoding` header value allows gzip, e.g. `gzip, deflate` or `*;q=0.5`.
+fn accepts_gzip(value: &str) -> bool {
+    value.split(',').any(|coding| {
+        let (name, parameters) = coding.split_once(';').unwrap_or((coding, ""));
+        let name = name.trim();
+        let rejected = header_parameters(parameters)
+            .any(|(key, value)| key.eq_ignore_ascii_case("q") && value.parse::<f32>() == Ok(0.0));
+        (name.eq_ignore_ascii_case("gzip") || name == "*") && !rejected
+    })
+}
+
+impl<const SLICE_SIZE: usize> Connection for AssetConnection<SLICE_SIZE> {
+    fn header_received(&mut self, key: &str, value: &str) -> ReadResult {
+        let name = HeaderName::from(key);
+        // Which variant is sent depends on `Accept-Encoding`, which may still come
+        if let Some(asset) = self.asset {
+            self.identity.header_received(name, value, asset.etag);
+            self.gzip.header_received(name, value, asset.gzip_etag);
+        }
+        match name {
+            HeaderName::AcceptEncoding => self.accepts_gzip = accepts_gzip(value),
+            HeaderName::Range if self.method == HttpMethod::Get => {
+                self.range.header_received(key, value);
+            }
+            _ => {}
+        }
+        ReadResult::Ok
+    }
+
+    fn switch_from_headers_to_body(&mut self) -> ReadResult {
+        ReadResult::Ok
+    }
+
+    fn body_received(&mut self, _bytes: &[u8]) -> ReadResult {
+        ReadResult::Ok
+    }
+
+    fn poll_write(&mut self, writer: &mut dyn ResponseWriter) -> WriteResult {
+        let Some(asset) = self.asset else {
+            writer.write_status(404, "Not Found")?;
+            writer.write_content_length(0)?;
+            writer.end_headers_start_body()?;
+            return WriteResult::EndWriting;
+        };
+        if !matches!(self.method, HttpMethod::Get | HttpMethod::Head) {
+            writer.write_status(405, "Method Not Allowed")?;
+            writer.write_response_header("Allow", "GET, HEAD")?;
+            writer.write_content_length(0)?;
+            writer.end_headers_start_body()?;
+            return WriteResult::EndWriting;
+        }
+
+        let (body, etag, conditions) = self.variant(asset);
+        let Some(response) = &mut self.response else {
+            if conditions.not_modified {
+                let vary = asset.gzip.map(|_| "Accept-Encoding");
+                writer.write_not_modified(vary, etag, None)?;
+                return WriteResult::EndWriting;
+            }
+            let response = if conditions.range_outdated {
+                RangeResponse::full(body.len() as u64, asset.content_type)
+            } else {
+                self.range.response(body.len() as u64, asset.content_type)
+            };
+            response.write_headers(writer)?;
+            if asset.gzip.is_some() {
+                // caches have to store both variants
+                writer.write_response_header("Vary", "Accept-Encoding")?;
+                if self.accepts_gzip {
+                    writer.write_response_header("Content-Encoding", "gzip")?;
+                }
+            }
+            if let Some(etag) = etag {
+                writer.write_response_header("ETag", etag)?;
+            }
+            writer.end_headers_start_body()?;
+            if response.is_done() {
+                return WriteResult::EndWriting;
+            }
+            self.response = Some(response);
+            return WriteResult::Ok { bytes_written: 0 };
+        };
+
+        response.poll_body(writer, |writer, range| {
+            // leave room for the end of a multipart body, which may be written right after the last slice
+            let capacity = writer
+                .write_capacity()
+                .saturating_sub(CLOSE_DELIMITER_LENGTH as usize);
+            let end = range.end.min(range.start + SLICE_SIZE.min(capacity) as u64);
+            writer.write_bytes(&body[range.start as usize..end as usize])
+        })
+    }
+}
+
+#[test]
+fn test_asset_connection() {
+    use crate::test_utilities::{writer, TestConfig};
+    use crate::traits::ResponseState;
+
+    static ASSETS: StaticAssets = StaticAssets::new(&[
+        Asset::new("/", "text/html", b"<h1>Hello</h1>"),
+        Asset::new("/empty.txt", "text/plain", b""),
+    ]);
+    assert!(ASSETS.get("/missing").is_none());
+
+    let mut config = TestConfig::default();
+    let mut response = ResponseState::default();
+    let mut writer = writer(&mut config, &mut response);
+    let mut connection = ASSETS.connection::<4>(HttpMethod::Get, "/?v=2");
+    let mut polls = 1;
+    while !matches!(connection.poll_write(&mut writer), WriteResult::EndWriting) {
+        polls += 1;
+    }
+    // the headers, and then 4 slices of 4 bytes
+    assert_eq!(polls, 5);
+    assert_eq!(
+        core::str::from_utf8(&config.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nContent-Type: text/html\r\nContent-Length: 14\r\n\r\n\
+         <h1>Hello</h1>"
+    );
+}
+
+#[test]
+fn test_asset_request_headers() {
+    use crate::test_utilities::{writer, TestConfig};
+    use crate::traits::ResponseState;
+
+    assert!(accepts_gzip("gzip"));
+    assert!(accepts_gzip("deflate, GZIP;q=0.5"));
+    assert!(accepts_gzip("*"));
+    assert!(!accepts_gzip("gzip;q=0, br"));
+    assert!(!accepts_gzip("identity"));
+
+    static ASSET: Asset = Asset::new("/", "text/plain", b"plain")
+        .with_gzip(b"gz")
+        .with_etag("\"1234\"")
+        .with_gzip_etag("\"1234-gz\"");
+    for (headers, expected) in [
+        (
+            "Accept-Encoding: br, gzip",
+            "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\
+             Vary: Accept-Encoding\r\nContent-Encoding: gzip\r\nETag: \"1234-gz\"\r\n\r\ngz",
+        ),
+        (
+            // the `ETag` of the identity variant doesn't validate the gzip variant
+            "If-None-Match: \"1234\"\r\nAccept-Encoding: gzip",
+            "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\
+             Vary: Accept-Encoding\r\nContent-Encoding: gzip\r\nETag: \"1234-gz\"\r\n\r\ngz",
+        ),
+        (
+            "Accept-Encoding: br\r\nIf-None-Match: \"1234\"",
+            "HTTP/1.1 304 Not Modified\r\nVary: Accept-Encoding\r\nETag: \"1234\"\r\n\r\n",
+        ),
+        (
+            "Range: bytes=1-2\r\nIf-Range: \"1234\"",
+            "HTTP/1.1 206 Partial Content\r\nContent-Type: text/plain\r\nContent-Range: bytes 1-2/5\r\n\
+             Content-Length: 2\r\nVary: Accept-Encoding\r\nETag: \"1234\"\r\n\r\nla",
+        ),
+        (
+            "Range: bytes=1-2\r\nIf-Range: \"5678\"",
+            "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\
+             Vary: Accept-Encoding\r\nETag: \"1234\"\r\n\r\nplain",
+        ),
+    ] {
+        let mut config = TestConfig::default();
+        let mut response = ResponseState::default();
+        let mut writer = writer(&mut config, &mut response);
+        let mut connection = AssetConnection::<1024>::new(HttpMethod::Get, Some(&ASSET));
+        for header in headers.split("\r\n") {
+            let (key, value) = header.split_once(": ").unwrap();
+            connection.header_received(key, value);
+        }
+        while !matches!(connection.poll_write(&mut writer), WriteResult::EndWriting) {}
+        assert_eq!(core::str::from_utf8(&config.output).unwrap(), expected);
+    }
+}
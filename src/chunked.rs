@@ -0,0 +1,210 @@
+use crate::{Error, Result};
+
+/// Incremental decoder for request bodies with `Transfer-Encoding: chunked`.
+///
+/// The decoder does not buffer anything, it hands out slices of the data it is given. This means that the chunk
+/// framing can be split over multiple `data_received` calls at any point.
+///
+/// Trailer fields after the last chunk are read and discarded. The chunk size lines (with their extensions) and the
+/// trailer lines are limited to `max_line_length` bytes, and there can be at most `max_trailers` trailer fields.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ChunkedDecoder {
+    state: DecoderState,
+    /// The amount of bytes of the current size or trailer line that have been read.
+    line_length: usize,
+    max_line_length: usize,
+    /// The amount of trailer fields that may still be received.
+    trailers_left: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum DecoderState {
+    /// Reading the hexadecimal size of the next chunk
+    Size {
+        size: u64,
+        has_digits: bool,
+    },
+    /// Skipping any chunk extensions until the end of the size line
+    Extension {
+        size: u64,
+    },
+    /// Reading the data of the current chunk
+    Data {
+        remaining: u64,
+    },
+    /// Expecting the `\r\n` after the chunk data
+    DataCr,
+    DataLf,
+    /// Reading the trailer section after the last chunk. `empty_line` is true if the current line has no content yet
+    Trailer {
+        empty_line: bool,
+    },
+    Done,
+}
+
+impl ChunkedDecoder {
+    pub(crate) fn new(max_line_length: usize, max_trailers: usize) -> Self {
+        Self {
+            state: DecoderState::Size {
+                size: 0,
+                has_digits: false,
+            },
+            line_length: 0,
+            max_line_length,
+            trailers_left: max_trailers,
+        }
+    }
+
+    /// Returns `true` once the last chunk and the trailer section have been read.
+    pub(crate) fn is_done(&self) -> bool {
+        self.state == DecoderState::Done
+    }
+
+    /// Consume the chunk framing at the start of `data` and return the next slice of payload.
+    ///
+    /// Returns `Ok(None)` when `data` is exhausted or the body is done. In the latter case `data` contains whatever came
+    /// after the body.
+    pub(crate) fn decode<'a>(&mut self, data: &mut &'a [u8]) -> Result<Option<&'a [u8]>> {
+        while let Some((&byte, rest)) = data.split_first() {
+            match self.state {
+                DecoderState::Data { remaining } => {
+                    let len = remaining.min(data.len() as u64);
+                    let (payload, rest) = data.split_at(len as usize);
+                    *data = rest;
+                    self.state = if len == remaining {
+                        DecoderState::DataCr
+                    } else {
+                        DecoderState::Data {
+                            remaining: remaining - len,
+                        }
+                    };
+                    return Ok(Some(payload));
+                }
+                DecoderState::Done => break,
+                _ => {}
+            }
+            *data = rest;
+            self.line_length += 1;
+            if self.line_length > self.max_line_length {
+                return Err(match self.state {
+                    DecoderState::Trailer { .. } => Error::HttpHeaderTooLong,
+                    _ => Error::InvalidChunk,
+                });
+            }
+            if byte == b'\n' {
+                self.line_length = 0;
+            }
+            self.state = match (self.state, byte) {
+                (DecoderState::Size { size, .. }, b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F') => {
+                    let digit = (byte as char).to_digit(16).unwrap_or_default();
+                    let size = size
+                        .checked_mul(16)
+                        .and_then(|size| size.checked_add(u64::from(digit)))
+                        .ok_or(Error::InvalidChunk)?;
+                    DecoderState::Size {
+                        size,
+                        has_digits: true,
+                    }
+                }
+                (
+                    DecoderState::Size {
+                        size,
+                        has_digits: true,
+                    }
+                    | DecoderState::Extension { size },
+                    b'\n',
+                ) => {
+                    if size == 0 {
+                        DecoderState::Trailer { empty_line: true }
+                    } else {
+                        DecoderState::Data { remaining: size }
+                    }
+                }
+                (
+                    DecoderState::Size {
+                        size,
+                        has_digits: true,
+                    },
+                    b';' | b' ' | b'\t' | b'\r',
+                ) => DecoderState::Extension { size },
+                (DecoderState::Extension { size }, _) => DecoderState::Extension { size },
+                (DecoderState::DataCr, b'\r') => DecoderState::DataLf,
+                (DecoderState::DataCr | DecoderState::DataLf, b'\n') => DecoderState::Size {
+                    size: 0,
+                    has_digits: false,
+                },
+                (DecoderState::Trailer { empty_line: true }, b'\n') => DecoderState::Done,
+                (DecoderState::Trailer { .. }, b'\n') => {
+                    self.trailers_left = self
+                        .trailers_left
+                        .checked_sub(1)
+                        .ok_or(Error::TooManyHeaders)?;
+                    DecoderState::Trailer { empty_line: true }
+                }
+                (DecoderState::Trailer { empty_line }, b'\r') => {
+                    DecoderState::Trailer { empty_line }
+                }
+                (DecoderState::Trailer { .. }, _) => DecoderState::Trailer { empty_line: false },
+                _ => return Err(Error::InvalidChunk),
+            };
+        }
+        Ok(None)
+    }
+}
+
+#[test]
+fn test_chunked_decoder() {
+    fn decode_all(decoder: &mut ChunkedDecoder, mut data: &[u8], out: &mut heapless::Vec<u8, 64>) {
+        while let Some(payload) = decoder.decode(&mut data).unwrap() {
+            out.extend_from_slice(payload).unwrap();
+        }
+    }
+
+    let body = b"5\r\nhello\r\n6;name=value\r\n world\r\n0\r\nExpires: never\r\n\r\n";
+
+    let mut decoder = ChunkedDecoder::new(64, 4);
+    let mut out = heapless::Vec::new();
+    decode_all(&mut decoder, body, &mut out);
+    assert!(decoder.is_done());
+    assert_eq!(out, b"hello world");
+
+    let mut decoder = ChunkedDecoder::new(64, 4);
+    let mut out = heapless::Vec::new();
+    for byte in body.chunks(1) {
+        decode_all(&mut decoder, byte, &mut out);
+    }
+    assert!(decoder.is_done());
+    assert_eq!(out, b"hello world");
+
+    let mut data = &b"0\r\n\r\nGET"[..];
+    let mut decoder = ChunkedDecoder::new(64, 4);
+    assert_eq!(decoder.decode(&mut data), Ok(None));
+    assert!(decoder.is_done());
+    assert_eq!(data, b"GET");
+
+    assert_eq!(
+        ChunkedDecoder::new(64, 4).decode(&mut &b"xyz\r\n"[..]),
+        Err(Error::InvalidChunk)
+    );
+    assert_eq!(
+        ChunkedDecoder::new(64, 4).decode(&mut &b"\r\n"[..]),
+        Err(Error::InvalidChunk)
+    );
+
+    // the size line with its extensions and the trailer lines are limited like header lines
+    assert_eq!(
+        ChunkedDecoder::new(8, 4).decode(&mut &b"5;name=value\r\n"[..]),
+        Err(Error::InvalidChunk)
+    );
+    assert_eq!(
+        ChunkedDecoder::new(8, 4).decode(&mut &b"0\r\nExpires: never\r\n"[..]),
+        Err(Error::HttpHeaderTooLong)
+    );
+    assert_eq!(
+        ChunkedDecoder::new(64, 1).decode(&mut &b"0\r\nA: 1\r\nB: 2\r\n\r\n"[..]),
+        Err(Error::TooManyHeaders)
+    );
+    let mut decoder = ChunkedDecoder::new(8, 1);
+    assert_eq!(decoder.decode(&mut &b"0;a=b\r\nA: 1\r\n\r\n"[..]), Ok(None));
+    assert!(decoder.is_done());
+}
@@ -1,12 +1,114 @@
+use crate::chunked::ChunkedDecoder;
 use crate::http_utilities::HeaderLine;
-use crate::traits::{Connection, ReadResult};
-use crate::{Error, Result};
+use crate::traits::{Connection, ReadResult, ResponseState, ServerConfigWriter};
+use crate::{
+    ContentLength, Error, HeaderName, HttpMethod, HttpVersion, Result, ServerTimeouts, WriteResult,
+};
 use core::net::SocketAddr;
+use heapless::Vec;
 
-pub(crate) struct Client<T: Connection> {
+pub(crate) struct Client<
+    T: Connection,
+    const BUFFER_SIZE: usize,
+    const TX_BUFFER_SIZE: usize,
+    const PIPELINE_BUFFER_SIZE: usize,
+> {
     pub(crate) connection: Option<T>,
     pub(crate) state: ClientState,
     pub(crate) address: SocketAddr,
+    /// While reading the request line and headers, this holds the start of a line that was split over multiple
+    /// `data_received` calls.
+    buffer: Vec<u8, BUFFER_SIZE>,
+    /// While writing, this holds the data of the next (pipelined) request(s), which is processed after the response is
+    /// done.
+    pipeline: Vec<u8, PIPELINE_BUFFER_SIZE>,
+    /// The part of the response that the transport has not accepted yet.
+    tx: Vec<u8, TX_BUFFER_SIZE>,
+    request: RequestInfo,
+    response: ResponseState,
+    /// The amount of requests that have been completed on this connection.
+    pub(crate) requests_handled: usize,
+    /// The phase the timeout is running for, and the time it started. Set by `tick`, and cleared when there is
+    /// progress.
+    timer: Option<(TimeoutPhase, u64)>,
+    /// The value of `Server::activity` when something last happened on this connection.
+    pub(crate) last_activity: u64,
+}
+
+/// The timeout of `ServerTimeouts` that applies to a client.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub(crate) enum TimeoutPhase {
+    /// Waiting for the first byte of a request
+    Idle,
+    Headers,
+    Body,
+    Writing,
+}
+
+/// Information about the current request that the client needs to keep track of itself.
+struct RequestInfo {
+    /// The value of the `Content-Length` header, if any.
+    content_length: Option<u64>,
+    /// `true` if the request has a `Transfer-Encoding: chunked` header.
+    chunked: bool,
+    body: BodyFraming,
+    /// `false` if the client wants the connection to be closed, or the request was not completely read.
+    keep_alive: bool,
+    version: HttpVersion,
+    /// `true` for HEAD requests, which are handled like GET requests without sending the body.
+    head: bool,
+    /// `true` for OPTIONS requests while `ServerConfig::CORS` is set, these can turn out to be a CORS preflight.
+    options: bool,
+    /// `true` if the request has an `Origin` header.
+    origin: bool,
+    /// `true` if the request has an `Access-Control-Request-Method` header.
+    request_method: bool,
+    /// The error of `ServerConfig::new_connection` for an OPTIONS request, which is returned if the request is not a
+    /// CORS preflight.
+    connection_error: Option<Error>,
+    /// `true` for CORS preflights, which are answered by the `ServerConfig::CORS` policy instead of a connection. This
+    /// is known once all headers are read.
+    preflight: bool,
+    /// The allowed origin from the `Origin` header, see `ServerConfigWriter::cors_origin`.
+    cors_origin: Option<&'static str>,
+    /// The amount of headers that have been received.
+    headers: usize,
+    /// The amount of body bytes that may still be received, `None` if there is no limit.
+    body_allowance: Option<u64>,
+}
+
+/// How the end of the request body is determined.
+enum BodyFraming {
+    /// The body has a fixed length, this is the amount of bytes that have not been received yet.
+    Length(u64),
+    Chunked(ChunkedDecoder),
+}
+
+impl Default for RequestInfo {
+    fn default() -> Self {
+        Self {
+            content_length: None,
+            chunked: false,
+            body: BodyFraming::default(),
+            keep_alive: false,
+            version: HttpVersion::Http11,
+            head: false,
+            options: false,
+            origin: false,
+            request_method: false,
+            connection_error: None,
+            preflight: false,
+            cors_origin: None,
+            headers: 0,
+            body_allowance: None,
+        }
+    }
+}
+
+impl Default for BodyFraming {
+    fn default() -> Self {
+        Self::Length(0)
+    }
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
@@ -18,94 +120,591 @@ pub(crate) enum ClientState {
     Done,
 }
 
-impl<T: Connection> Client<T> {
+impl<
+        T: Connection,
+        const BUFFER_SIZE: usize,
+        const TX_BUFFER_SIZE: usize,
+        const PIPELINE_BUFFER_SIZE: usize,
+    > Client<T, BUFFER_SIZE, TX_BUFFER_SIZE, PIPELINE_BUFFER_SIZE>
+{
     pub(crate) fn new(address: SocketAddr) -> Self {
         Self {
             connection: None,
             state: ClientState::ReadingRequestLine,
             address,
+            buffer: Vec::new(),
+            pipeline: Vec::new(),
+            tx: Vec::new(),
+            request: RequestInfo::default(),
+            response: ResponseState::default(),
+            requests_handled: 0,
+            timer: None,
+            last_activity: 0,
+        }
+    }
+
+    /// The HTTP version of the current request, HTTP/1.1 until the request line is read.
+    pub(crate) fn version(&self) -> HttpVersion {
+        self.request.version
+    }
+
+    /// Returns `true` if the client is waiting for a new request, and has not sent any part of it.
+    pub(crate) fn is_idle(&self) -> bool {
+        self.timeout_phase() == Some(TimeoutPhase::Idle)
+    }
+
+    fn timeout_phase(&self) -> Option<TimeoutPhase> {
+        match self.state {
+            ClientState::ReadingRequestLine if self.buffer.is_empty() => Some(TimeoutPhase::Idle),
+            ClientState::ReadingRequestLine | ClientState::ReadingHeaders => {
+                Some(TimeoutPhase::Headers)
+            }
+            ClientState::ReadingBody => Some(TimeoutPhase::Body),
+            ClientState::Writing => Some(TimeoutPhase::Writing),
+            ClientState::Done => None,
         }
     }
 
+    /// Returns the phase that timed out, if any.
+    pub(crate) fn tick(&mut self, now: u64, timeouts: &ServerTimeouts) -> Option<TimeoutPhase> {
+        let phase = self.timeout_phase()?;
+        let started = match self.timer {
+            Some((timer_phase, started)) if timer_phase == phase => started,
+            _ => {
+                self.timer = Some((phase, now));
+                now
+            }
+        };
+        let timeout = match phase {
+            TimeoutPhase::Idle => timeouts.idle,
+            TimeoutPhase::Headers => timeouts.header_read,
+            TimeoutPhase::Body => timeouts.body_read,
+            TimeoutPhase::Writing => timeouts.write,
+        }?;
+        (now.saturating_sub(started) >= timeout).then_some(phase)
+    }
+
     pub(crate) fn data_received<S>(&mut self, config: &mut S, mut data: &[u8]) -> Result
     where
         S: crate::ServerConfig<ConnectionType = T>,
     {
         loop {
-            return match (self.state, &mut self.connection) {
-                (ClientState::ReadingRequestLine, None) => {
-                    let (method, path, remaining) =
-                        crate::http_utilities::read_http_request_line(data)?;
-                    let connection = config.new_connection(self.address, method, path)?;
-                    self.connection = Some(connection);
-                    self.state = ClientState::ReadingHeaders;
+            return match self.state {
+                ClientState::ReadingRequestLine | ClientState::ReadingHeaders => {
+                    let Some(end) = data.iter().position(|b| *b == b'\n') else {
+                        // No full line yet, keep what we have until the rest comes in
+                        self.check_line_length::<S>(self.buffer.len() + data.len())?;
+                        return self.buffer_partial_line(data);
+                    };
+                    let (line, remaining) = data.split_at(end + 1);
                     data = remaining;
-                    continue;
+                    self.check_line_length::<S>(self.buffer.len() + line.len())?;
+
+                    let line = if self.buffer.is_empty() {
+                        line
+                    } else {
+                        self.buffer_partial_line(line)?;
+                        &self.buffer
+                    };
+                    let result = Self::process_line(
+                        &mut self.state,
+                        &mut self.connection,
+                        &mut self.request,
+                        self.address,
+                        config,
+                        line,
+                    );
+                    self.buffer.clear();
+                    result?;
+                    if data.is_empty() {
+                        Ok(())
+                    } else {
+                        continue;
+                    }
+                }
+                ClientState::ReadingBody => {
+                    // the body timeout is restarted whenever part of the body comes in
+                    self.timer = None;
+                    data = self.body_received(data)?;
+                    if data.is_empty() {
+                        Ok(())
+                    } else {
+                        continue;
+                    }
+                }
+                ClientState::Writing if self.request.keep_alive && !self.response.close => {
+                    if self.pipeline.extend_from_slice(data).is_err() {
+                        // Too many pipelined requests. The connection is closed after the current response, the
+                        // client sends the requests that were not answered again on a new connection.
+                        self.request.keep_alive = false;
+                        self.pipeline.clear();
+                    }
+                    Ok(())
+                }
+                ClientState::Writing | ClientState::Done => {
+                    // the connection will be closed after this response, so there is no next request to read
+                    Ok(())
+                }
+            };
+        }
+    }
+
+    /// Pass the body in `data` to the connection. Returns any data after the end of the body.
+    fn body_received<'a>(&mut self, mut data: &'a [u8]) -> Result<&'a [u8]> {
+        // Without a connection (for a CORS preflight), the body is read and dropped
+        let mut body_received = |body: &[u8]| {
+            self.connection
+                .as_mut()
+                .map_or(ReadResult::Ok, |c| c.body_received(body))
+        };
+        let result = match &mut self.request.body {
+            BodyFraming::Length(remaining) => {
+                // `remaining` can be larger than `usize` on 32 bit targets
+                let remaining_len = usize::try_from(*remaining).unwrap_or(usize::MAX);
+                let (body, rest) = data.split_at(data.len().min(remaining_len));
+                data = rest;
+                *remaining -= body.len() as u64;
+                match body_received(body) {
+                    ReadResult::Ok if *remaining == 0 => ReadResult::EndReading,
+                    ReadResult::EndReading if *remaining > 0 => {
+                        // the rest of the body is still coming, so the next request can't be read from this connection
+                        self.request.keep_alive = false;
+                        ReadResult::EndReading
+                    }
+                    result => result,
+                }
+            }
+            BodyFraming::Chunked(decoder) => {
+                let mut result = ReadResult::Ok;
+                while let Some(payload) = decoder.decode(&mut data)? {
+                    // The length of a chunked body is not known up front, so it is checked while reading
+                    if let Some(allowance) = &mut self.request.body_allowance {
+                        *allowance = allowance
+                            .checked_sub(payload.len() as u64)
+                            .ok_or(Error::BodyTooLong)?;
+                    }
+                    result = body_received(payload);
+                    if !matches!(result, ReadResult::Ok) {
+                        break;
+                    }
+                }
+                if decoder.is_done() {
+                    result = ReadResult::EndReading;
+                } else if matches!(result, ReadResult::EndReading) {
+                    self.request.keep_alive = false;
+                }
+                result
+            }
+        };
+        match result {
+            ReadResult::Ok => Ok(data),
+            ReadResult::Err(e) => Err(e),
+            ReadResult::EndReading => {
+                self.state = ClientState::Writing;
+                Ok(data)
+            }
+        }
+    }
+
+    /// Returns an error if a request line or header line of `length` bytes is longer than `ServerConfig::LIMITS`
+    /// allows.
+    ///
+    /// Lines are also limited to `BUFFER_SIZE`, whether they were split over multiple `data_received` calls (and have to
+    /// be buffered) or not.
+    fn check_line_length<S: crate::ServerConfig>(&self, length: usize) -> Result {
+        match self.state {
+            ClientState::ReadingRequestLine
+                if length > S::LIMITS.max_request_line_length.min(BUFFER_SIZE) =>
+            {
+                Err(Error::RequestLineTooLong)
+            }
+            ClientState::ReadingHeaders
+                if length > S::LIMITS.max_header_length.min(BUFFER_SIZE) =>
+            {
+                Err(Error::HttpHeaderTooLong)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn buffer_partial_line(&mut self, data: &[u8]) -> Result {
+        self.buffer
+            .extend_from_slice(data)
+            .map_err(|()| match self.state {
+                ClientState::ReadingRequestLine => Error::RequestLineTooLong,
+                _ => Error::HttpHeaderTooLong,
+            })
+    }
+
+    /// Handle a single, complete line (including the trailing `\n`).
+    ///
+    /// This takes the fields separately so `line` can borrow from `Client::buffer`.
+    fn process_line<S>(
+        state: &mut ClientState,
+        connection: &mut Option<T>,
+        request: &mut RequestInfo,
+        address: SocketAddr,
+        config: &mut S,
+        line: &[u8],
+    ) -> Result
+    where
+        S: crate::ServerConfig<ConnectionType = T>,
+    {
+        match (*state, connection) {
+            (ClientState::ReadingRequestLine, connection @ None) => {
+                // RFC 9112 section 2.2: empty lines before a request line are ignored, some clients send one after a
+                // request body
+                if matches!(line, b"\r\n" | b"\n") {
+                    return Ok(());
+                }
+                let (method, path, version) = crate::http_utilities::read_http_request_line(line)?;
+                // A HEAD response has the same headers as a GET response, the body is dropped by `ServerConfigWriter`
+                request.head = method == HttpMethod::Head;
+                let method = if request.head {
+                    HttpMethod::Get
+                } else {
+                    method
+                };
+                // With a CORS policy, an OPTIONS request may be a preflight that the server answers itself
+                request.options = method == HttpMethod::Options && S::CORS.is_some();
+                match config.new_connection(address, method, path, version) {
+                    Ok(new) => *connection = Some(new),
+                    Err(e) if request.options => request.connection_error = Some(e),
+                    Err(e) => return Err(e),
                 }
-                (ClientState::ReadingHeaders, Some(connection)) => {
-                    match crate::http_utilities::read_header_line(data) {
-                        HeaderLine::Error(e) => Err(e),
-                        HeaderLine::Whiteline { remaining } => {
-                            match connection.switch_from_headers_to_body() {
-                                ReadResult::Ok => {
-                                    self.state = ClientState::ReadingBody;
-                                    data = remaining;
-                                    continue;
+                *state = ClientState::ReadingHeaders;
+                request.version = version;
+                // HTTP/1.0 connections are closed after the response, unless the client sends `Connection: keep-alive`
+                request.keep_alive = version != HttpVersion::Http10;
+                Ok(())
+            }
+            (ClientState::ReadingHeaders, connection) => {
+                let result = match crate::http_utilities::read_header_line(line) {
+                    HeaderLine::Error(e) => return Err(e),
+                    HeaderLine::Whiteline => {
+                        request.preflight =
+                            request.options && request.origin && request.request_method;
+                        if request.preflight {
+                            *connection = None;
+                        } else if let Some(e) = request.connection_error.take() {
+                            return Err(e);
+                        }
+                        request.body = match (request.chunked, request.content_length) {
+                            // Trailer fields count towards the header limits
+                            (true, None) => BodyFraming::Chunked(ChunkedDecoder::new(
+                                S::LIMITS.max_header_length.min(BUFFER_SIZE),
+                                S::LIMITS.max_headers.saturating_sub(request.headers),
+                            )),
+                            // A request with both is ambiguous, and can be used to smuggle requests past proxies
+                            (true, Some(_)) => return Err(Error::InvalidTransferEncoding),
+                            (false, length) => BodyFraming::Length(length.unwrap_or(0)),
+                        };
+                        request.body_allowance = connection
+                            .as_ref()
+                            .and_then(|c| c.max_body_size())
+                            .or(S::LIMITS.max_body_size);
+                        // Reject a body that is too large before any of it is read
+                        if let (Some(length), Some(allowance)) =
+                            (request.content_length, request.body_allowance)
+                        {
+                            if length > allowance {
+                                return Err(Error::BodyTooLong);
+                            }
+                        }
+                        *state = ClientState::ReadingBody;
+                        let has_body = !matches!(request.body, BodyFraming::Length(0));
+                        let result = connection
+                            .as_mut()
+                            .map_or(ReadResult::Ok, |c| c.switch_from_headers_to_body());
+                        match result {
+                            // Requests without a body go straight to writing a response
+                            ReadResult::Ok if !has_body => ReadResult::EndReading,
+                            ReadResult::EndReading if has_body => {
+                                request.keep_alive = false;
+                                ReadResult::EndReading
+                            }
+                            result => result,
+                        }
+                    }
+                    HeaderLine::Header { key, value } => {
+                        request.headers += 1;
+                        if request.headers > S::LIMITS.max_headers {
+                            return Err(Error::TooManyHeaders);
+                        }
+                        match HeaderName::from(key) {
+                            HeaderName::ContentLength => {
+                                let ContentLength(length) = value.parse()?;
+                                if request.content_length.is_some_and(|l| l != length) {
+                                    return Err(Error::InvalidContentLength);
                                 }
-                                ReadResult::Err(e) => Err(e),
-                                ReadResult::EndReading => {
-                                    self.state = ClientState::Writing;
-                                    Ok(())
+                                request.content_length = Some(length);
+                            }
+                            HeaderName::TransferEncoding => {
+                                // `chunked` has to be the last encoding, and we don't support any other encodings
+                                if !value.eq_ignore_ascii_case("chunked") {
+                                    return Err(Error::InvalidTransferEncoding);
                                 }
+                                request.chunked = true;
                             }
-                        }
-                        HeaderLine::Header {
-                            key,
-                            value,
-                            remaining,
-                        } => match connection.header_received(key, value) {
-                            ReadResult::Ok => {
-                                data = remaining;
-                                continue;
+                            HeaderName::AccessControlRequestMethod => request.request_method = true,
+                            HeaderName::Origin => {
+                                request.origin = true;
+                                request.cors_origin = S::CORS
+                                    .as_ref()
+                                    .and_then(|policy| policy.allowed_origin(value));
                             }
-                            ReadResult::Err(e) => Err(e),
+                            HeaderName::Connection => {
+                                for option in value.split(',').map(str::trim) {
+                                    if option.eq_ignore_ascii_case("close") {
+                                        request.keep_alive = false;
+                                    } else if option.eq_ignore_ascii_case("keep-alive") {
+                                        request.keep_alive = true;
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                        let result = connection
+                            .as_mut()
+                            .map_or(ReadResult::Ok, |c| c.header_received(key, value));
+                        match result {
                             ReadResult::EndReading => {
-                                self.state = ClientState::Writing;
-                                Ok(())
+                                // we stop reading halfway through the headers
+                                request.keep_alive = false;
+                                ReadResult::EndReading
                             }
-                        },
-                    }
-                }
-                (ClientState::ReadingBody, Some(connection)) => {
-                    match connection.body_received(data) {
-                        ReadResult::Ok => Ok(()),
-                        ReadResult::Err(e) => Err(e),
-                        ReadResult::EndReading => {
-                            self.state = ClientState::Writing;
-                            Ok(())
+                            result => result,
                         }
                     }
+                };
+                match result {
+                    ReadResult::Ok => Ok(()),
+                    ReadResult::Err(e) => Err(e),
+                    ReadResult::EndReading => {
+                        *state = ClientState::Writing;
+                        Ok(())
+                    }
                 }
-                (ClientState::Writing | ClientState::Done, _) => {
-                    // do nothing
-                    Ok(())
-                }
-                _ => Err(Error::InvalidClientState),
+            }
+            _ => Err(Error::InvalidClientState),
+        }
+    }
+
+    /// Send the bytes that the transport didn't accept earlier. Returns `WriteResult::WouldBlock` until all of them
+    /// are sent.
+    fn flush<S: crate::ServerConfig<ConnectionType = T>>(&mut self, config: &mut S) -> WriteResult {
+        while !self.tx.is_empty() {
+            let written = match config.write(self.address, &self.tx) {
+                WriteResult::Ok { bytes_written } => bytes_written.min(self.tx.len()),
+                WriteResult::WouldBlock => 0,
+                WriteResult::Err(e) => return WriteResult::Err(e),
+                WriteResult::EndWriting => return WriteResult::Err(Error::InvalidClientState),
             };
+            if written == 0 {
+                return WriteResult::WouldBlock;
+            }
+            // the write timeout is restarted whenever the response makes progress
+            self.timer = None;
+            self.tx.copy_within(written.., 0);
+            self.tx.truncate(self.tx.len() - written);
         }
+        WriteResult::Ok { bytes_written: 0 }
     }
 
     pub(crate) fn poll_write<S: crate::ServerConfig<ConnectionType = T>>(
         &mut self,
         config: &mut S,
-    ) -> crate::WriteResult {
+    ) -> WriteResult {
+        // The rest of the previous poll goes out first, the connection continues where it left off after that
+        self.flush(config)?;
+        if self.response.ended {
+            return WriteResult::EndWriting;
+        }
+        let result = self.poll_connection(config);
+        // the write timeout is restarted whenever the response makes progress, including when it is done
+        match result {
+            WriteResult::Ok { bytes_written } if bytes_written > 0 => self.timer = None,
+            WriteResult::EndWriting => {
+                self.timer = None;
+                self.response.ended = true;
+                self.flush(config)?;
+            }
+            _ => {}
+        }
+        result
+    }
+
+    fn poll_connection<S: crate::ServerConfig<ConnectionType = T>>(
+        &mut self,
+        config: &mut S,
+    ) -> WriteResult {
+        let mut writer = ServerConfigWriter {
+            config,
+            addr: self.address,
+            response: &mut self.response,
+            keep_alive: self.request.keep_alive
+                && self.requests_handled + 1 < S::MAX_REQUESTS_PER_CONNECTION,
+            version: self.request.version,
+            head: self.request.head,
+            // the preflight response adds the CORS headers itself
+            cors_origin: self.request.cors_origin.filter(|_| !self.request.preflight),
+            tx: Some(&mut self.tx),
+        };
         if let Some(connection) = &mut self.connection {
-            connection.poll_write(&mut crate::traits::ServerConfigWriter {
-                config,
-                addr: self.address,
-            })
+            match connection.poll_write(&mut writer) {
+                WriteResult::EndWriting => writer.finish(),
+                result @ WriteResult::Ok { .. } => {
+                    // the body of a HEAD response is dropped anyway, so the connection doesn't have to write it
+                    if writer.head && writer.response.body_started {
+                        writer.finish()
+                    } else {
+                        result
+                    }
+                }
+                result => result,
+            }
+        } else if let (true, Some(policy)) = (self.request.preflight, &S::CORS) {
+            policy.write_preflight(&mut writer, self.request.cors_origin)?;
+            WriteResult::EndWriting
+        } else {
+            WriteResult::EndWriting
+        }
+    }
+
+    /// Called after the response is written. Either gets the client ready for the next request, or closes the
+    /// connection.
+    ///
+    /// Any pipelined requests that came in while writing the response are processed here.
+    pub(crate) fn finish_response<S: crate::ServerConfig<ConnectionType = T>>(
+        &mut self,
+        config: &mut S,
+    ) -> Result {
+        if let Some(connection) = &mut self.connection {
+            config.on_client_done_writing(self.address, connection);
+        }
+        self.requests_handled += 1;
+        let keep_alive = self.request.keep_alive
+            && !self.response.close
+            && self.requests_handled < S::MAX_REQUESTS_PER_CONNECTION;
+        if keep_alive {
+            self.connection = None;
+            self.state = ClientState::ReadingRequestLine;
+            self.request = RequestInfo::default();
+            self.response = ResponseState::default();
+            let pending = core::mem::take(&mut self.pipeline);
+            self.data_received(config, &pending)
         } else {
-            crate::WriteResult::EndWriting
+            self.state = ClientState::Done;
+            config.close_connection(self.address);
+            Ok(())
         }
     }
 }
+
+#[test]
+fn test_fragmented_request() {
+    use crate::test_utilities::{TestConfig, ADDR};
+
+    let request = b"GET /foo/bar HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n";
+    let mut config = TestConfig::default();
+    let mut client = Client::<_, 64, 64, 64>::new(ADDR);
+    for byte in request.chunks(1) {
+        client.data_received(&mut config, byte).unwrap();
+    }
+    assert_eq!(client.state, ClientState::Writing);
+    let connection = client.connection.as_ref().unwrap();
+    assert_eq!(connection.method, crate::HttpMethod::Get);
+    assert_eq!(connection.path, "/foo/bar");
+    assert_eq!(connection.headers.len(), 2);
+    assert_eq!(
+        connection.headers[0],
+        ("Host".try_into().unwrap(), "localhost".try_into().unwrap())
+    );
+    assert_eq!(
+        connection.headers[1],
+        ("Accept".try_into().unwrap(), "*/*".try_into().unwrap())
+    );
+
+    let mut client = Client::<_, 8, 64, 64>::new(ADDR);
+    assert_eq!(
+        client.data_received(&mut config, b"GET /a/very/long/path"),
+        Err(Error::RequestLineTooLong)
+    );
+
+    // the same limit applies to a line that doesn't have to be buffered
+    let mut client = Client::<_, 24, 64, 64>::new(ADDR);
+    assert_eq!(
+        client.data_received(&mut config, b"GET /a/very/long/path HTTP/1.1\r\n"),
+        Err(Error::RequestLineTooLong)
+    );
+    let mut client = Client::<_, 24, 64, 64>::new(ADDR);
+    assert_eq!(
+        client.data_received(
+            &mut config,
+            b"GET / HTTP/1.1\r\nX-Long-Header: a much longer value\r\n"
+        ),
+        Err(Error::HttpHeaderTooLong)
+    );
+}
+
+#[test]
+fn test_content_length() {
+    use crate::test_utilities::{TestConfig, ADDR};
+
+    let mut config = TestConfig::default();
+    let mut client = Client::<_, 64, 64, 64>::new(ADDR);
+    client
+        .data_received(
+            &mut config,
+            b"POST / HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello",
+        )
+        .unwrap();
+    assert_eq!(client.state, ClientState::ReadingBody);
+    client.data_received(&mut config, b" world").unwrap();
+    assert_eq!(client.state, ClientState::Writing);
+    assert_eq!(client.connection.as_ref().unwrap().body, b"hello world");
+
+    let mut client = Client::<_, 64, 64, 64>::new(ADDR);
+    client
+        .data_received(&mut config, b"POST / HTTP/1.1\r\n\r\n")
+        .unwrap();
+    assert_eq!(client.state, ClientState::Writing);
+
+    // a sign is accepted by `u64::from_str`, but a proxy may read the header differently
+    for length in ["+5", "-5", ""] {
+        let mut client = Client::<_, 64, 64, 64>::new(ADDR);
+        let mut request: heapless::String<64> = heapless::String::new();
+        core::fmt::write(
+            &mut request,
+            format_args!("POST / HTTP/1.1\r\nContent-Length: {length}\r\n\r\nhello"),
+        )
+        .unwrap();
+        assert_eq!(
+            client.data_received(&mut config, request.as_bytes()),
+            Err(Error::InvalidContentLength)
+        );
+    }
+}
+
+#[test]
+fn test_chunked_body() {
+    use crate::test_utilities::{TestConfig, ADDR};
+
+    let request = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+    let mut config = TestConfig::default();
+    let mut client = Client::<_, 64, 64, 64>::new(ADDR);
+    for byte in request.chunks(3) {
+        client.data_received(&mut config, byte).unwrap();
+    }
+    assert_eq!(client.state, ClientState::Writing);
+    assert_eq!(client.connection.as_ref().unwrap().body, b"hello world");
+
+    let mut client = Client::<_, 64, 64, 64>::new(ADDR);
+    assert_eq!(
+        client.data_received(
+            &mut config,
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n"
+        ),
+        Err(Error::InvalidTransferEncoding)
+    );
+}
@@ -0,0 +1,364 @@
+use crate::{Error, HeaderName, Result};
+use core::{fmt, str::FromStr};
+use heapless::String;
+
+/// Captures the `If-None-Match` and `If-Modified-Since` headers of a request, so the response can be short-circuited
+/// to `304 Not Modified` with `ResponseWriter::write_not_modified`.
+///
+/// Pass every header from `Connection::header_received` to `header_received`, and call `is_not_modified` with the
+/// validators of the response once they are known. `If-None-Match` values longer than `ETAG_SIZE` never match.
+///
+/// This is only meaningful for `GET` and `HEAD` requests.
+pub struct ConditionalHeaders<const ETAG_SIZE: usize = 64> {
+    if_none_match: IfNoneMatch<ETAG_SIZE>,
+    if_modified_since: Option<HttpDate>,
+}
+
+enum IfNoneMatch<const ETAG_SIZE: usize> {
+    Missing,
+    Value(String<ETAG_SIZE>),
+    /// The header was sent, but did not fit in the buffer
+    TooLong,
+}
+
+impl<const ETAG_SIZE: usize> ConditionalHeaders<ETAG_SIZE> {
+    pub const fn new() -> Self {
+        Self {
+            if_none_match: IfNoneMatch::Missing,
+            if_modified_since: None,
+        }
+    }
+
+    /// Returns `true` if the header was one of the conditional headers.
+    pub fn header_received(&mut self, key: &str, value: &str) -> bool {
+        let name = HeaderName::from(key);
+        if name == HeaderName::IfNoneMatch {
+            // a header that is sent multiple times is the same as a single comma separated list
+            self.if_none_match =
+                match core::mem::replace(&mut self.if_none_match, IfNoneMatch::TooLong) {
+                    IfNoneMatch::Missing => {
+                        String::try_from(value).map_or(IfNoneMatch::TooLong, IfNoneMatch::Value)
+                    }
+                    IfNoneMatch::Value(mut list) => {
+                        if list.push_str(", ").is_ok() && list.push_str(value).is_ok() {
+                            IfNoneMatch::Value(list)
+                        } else {
+                            IfNoneMatch::TooLong
+                        }
+                    }
+                    IfNoneMatch::TooLong => IfNoneMatch::TooLong,
+                };
+            true
+        } else if name == HeaderName::IfModifiedSince {
+            // RFC 9110 section 13.1.3: an invalid date is ignored
+            self.if_modified_since = value.parse().ok();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns `true` if the client already has the response with these validators, so `304 Not Modified` can be
+    /// sent instead.
+    ///
+    /// `If-Modified-Since` is only used if the request has no `If-None-Match` header (RFC 9110 section 13.2.2).
+    pub fn is_not_modified(&self, etag: Option<&str>, last_modified: Option<HttpDate>) -> bool {
+        match &self.if_none_match {
+            IfNoneMatch::Value(list) => etag.is_some_and(|etag| etag_matches(list, etag)),
+            IfNoneMatch::TooLong => false,
+            IfNoneMatch::Missing => match (self.if_modified_since, last_modified) {
+                (Some(since), Some(last_modified)) => last_modified <= since,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl<const ETAG_SIZE: usize> Default for ConditionalHeaders<ETAG_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns `true` if `etag` is in the `If-None-Match` header value `list`, using the weak comparison.
+pub(crate) fn etag_matches(list: &str, etag: &str) -> bool {
+    fn opaque(tag: &str) -> &str {
+        let tag = tag.trim();
+        tag.strip_prefix("W/").unwrap_or(tag)
+    }
+    list.trim() == "*" || list.split(',').any(|tag| opaque(tag) == opaque(etag))
+}
+
+/// A timestamp as used in the `Last-Modified` and `If-Modified-Since` headers, with a precision of one second.
+///
+/// This formats the preferred format from RFC 9110 section 5.6.7, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`, and also parses
+/// the obsolete RFC 850 and asctime formats.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HttpDate {
+    unix_timestamp: u64,
+}
+
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const FULL_DAY_NAMES: [&str; 7] = [
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+];
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+impl HttpDate {
+    pub const fn from_unix_timestamp(unix_timestamp: u64) -> Self {
+        Self { unix_timestamp }
+    }
+
+    /// The amount of seconds since 1970-01-01 00:00:00 UTC.
+    pub const fn unix_timestamp(&self) -> u64 {
+        self.unix_timestamp
+    }
+}
+
+// The conversions between days and dates are from http://howardhinnant.github.io/date_algorithms.html
+
+/// The amount of days since 1970-01-01 of the given date, `month` and `day` start at 1.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The `(year, month, day)` of the amount of days since 1970-01-01.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+impl FromStr for HttpDate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        fn number(s: &str, digits: usize, max: u64) -> Result<u64> {
+            if s.len() != digits || !s.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(Error::InvalidHttpDate);
+            }
+            let number = s.parse().map_err(|_| Error::InvalidHttpDate)?;
+            if number > max {
+                return Err(Error::InvalidHttpDate);
+            }
+            Ok(number)
+        }
+
+        // RFC 9110 section 5.6.7: recipients also have to accept the obsolete RFC 850 and asctime formats
+        let (day_name_valid, day, month, year, time) = if let Some((day_name, rest)) =
+            s.split_once(", ")
+        {
+            let rest = rest.strip_suffix(" GMT").ok_or(Error::InvalidHttpDate)?;
+            if let Some((date, time)) = rest.split_once(' ').filter(|_| day_name.len() > 3) {
+                // RFC 850: `Sunday, 06-Nov-94 08:49:37 GMT`
+                let mut date = date.split('-');
+                let (Some(day), Some(month), Some(year), None) =
+                    (date.next(), date.next(), date.next(), date.next())
+                else {
+                    return Err(Error::InvalidHttpDate);
+                };
+                // two digit years are read as 1970 to 2069, earlier dates can't be represented anyway
+                let year = number(year, 2, 99)?;
+                let year = if year < 70 { year + 2000 } else { year + 1900 };
+                (
+                    FULL_DAY_NAMES.contains(&day_name),
+                    number(day, 2, 31)?,
+                    month,
+                    year,
+                    time,
+                )
+            } else {
+                // IMF-fixdate: `Sun, 06 Nov 1994 08:49:37 GMT`
+                let mut parts = rest.split(' ');
+                let (Some(day), Some(month), Some(year), Some(time), None) = (
+                    parts.next(),
+                    parts.next(),
+                    parts.next(),
+                    parts.next(),
+                    parts.next(),
+                ) else {
+                    return Err(Error::InvalidHttpDate);
+                };
+                (
+                    DAY_NAMES.contains(&day_name),
+                    number(day, 2, 31)?,
+                    month,
+                    number(year, 4, 9999)?,
+                    time,
+                )
+            }
+        } else {
+            // asctime: `Sun Nov  6 08:49:37 1994`, single digit days are padded with a space
+            let mut parts = s.split(' ');
+            let (Some(day_name), Some(month), Some(mut day)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(Error::InvalidHttpDate);
+            };
+            let digits = if day.is_empty() {
+                day = parts.next().ok_or(Error::InvalidHttpDate)?;
+                1
+            } else {
+                2
+            };
+            let (Some(time), Some(year), None) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(Error::InvalidHttpDate);
+            };
+            (
+                DAY_NAMES.contains(&day_name),
+                number(day, digits, 31)?,
+                month,
+                number(year, 4, 9999)?,
+                time,
+            )
+        };
+        let mut time = time.split(':');
+        let (Some(hour), Some(minute), Some(second), None) =
+            (time.next(), time.next(), time.next(), time.next())
+        else {
+            return Err(Error::InvalidHttpDate);
+        };
+
+        if !day_name_valid {
+            return Err(Error::InvalidHttpDate);
+        }
+        let month = MONTH_NAMES
+            .iter()
+            .position(|&name| name == month)
+            .ok_or(Error::InvalidHttpDate)? as u64
+            + 1;
+        if day == 0 || year < 1970 {
+            return Err(Error::InvalidHttpDate);
+        }
+        let days = days_from_civil(year, month, day);
+        // the seconds go up to 60 for leap seconds
+        let seconds =
+            number(hour, 2, 23)? * 3600 + number(minute, 2, 59)? * 60 + number(second, 2, 60)?;
+        Ok(Self::from_unix_timestamp(days * 86400 + seconds))
+    }
+}
+
+impl fmt::Display for HttpDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days = self.unix_timestamp / 86400;
+        let seconds = self.unix_timestamp % 86400;
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{}, {day:02} {} {year:04} {:02}:{:02}:{:02} GMT",
+            DAY_NAMES[(days % 7) as usize],
+            MONTH_NAMES[month as usize - 1],
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+#[test]
+fn test_http_date() {
+    let date: HttpDate = "Sun, 06 Nov 1994 08:49:37 GMT".parse().unwrap();
+    assert_eq!(date.unix_timestamp(), 784111777);
+    let mut formatted = String::<32>::new();
+    core::fmt::write(&mut formatted, format_args!("{date}")).unwrap();
+    assert_eq!(formatted, "Sun, 06 Nov 1994 08:49:37 GMT");
+
+    let mut formatted = String::<32>::new();
+    core::fmt::write(
+        &mut formatted,
+        format_args!("{}", HttpDate::from_unix_timestamp(951782400)),
+    )
+    .unwrap();
+    assert_eq!(formatted, "Tue, 29 Feb 2000 00:00:00 GMT");
+
+    for obsolete in ["Sunday, 06-Nov-94 08:49:37 GMT", "Sun Nov  6 08:49:37 1994"] {
+        assert_eq!(obsolete.parse::<HttpDate>(), Ok(date));
+    }
+    let date: HttpDate = "Thursday, 01-Jan-04 00:00:00 GMT".parse().unwrap();
+    assert_eq!(date.unix_timestamp(), 1072915200);
+    let date: HttpDate = "Wed Nov 16 08:49:37 1994".parse().unwrap();
+    assert_eq!(date.unix_timestamp(), 784111777 + 10 * 86400);
+
+    for invalid in [
+        "Sun, 06-Nov-94 08:49:37 GMT",
+        "Sunday, 06 Nov 1994 08:49:37 GMT",
+        "Sun Nov 6 08:49:37 1994",
+        "Sun Nov  06 08:49:37 1994",
+        "Sun, 06 Nov 1994 08:49:37 UTC",
+        "Sun, 6 Nov 1994 08:49:37 GMT",
+        "Sun, 06 Nov 1994 24:49:37 GMT",
+    ] {
+        assert_eq!(invalid.parse::<HttpDate>(), Err(Error::InvalidHttpDate));
+    }
+}
+
+#[test]
+fn test_conditional_headers() {
+    let last_modified = HttpDate::from_unix_timestamp(784111777);
+
+    let mut headers = ConditionalHeaders::<64>::new();
+    assert!(!headers.is_not_modified(Some("\"a\""), Some(last_modified)));
+    assert!(!headers.header_received("Accept", "*/*"));
+    assert!(headers.header_received("If-None-Match", "\"a\", W/\"b\""));
+    assert!(headers.header_received("If-None-Match", "\"c\""));
+    assert!(headers.is_not_modified(Some("\"a\""), None));
+    assert!(headers.is_not_modified(Some("W/\"b\""), None));
+    assert!(headers.is_not_modified(Some("\"c\""), None));
+    assert!(!headers.is_not_modified(Some("\"d\""), None));
+    assert!(!headers.is_not_modified(None, None));
+
+    // If-None-Match takes precedence over If-Modified-Since
+    headers.header_received("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT");
+    assert!(!headers.is_not_modified(Some("\"d\""), Some(last_modified)));
+
+    let mut headers = ConditionalHeaders::<64>::new();
+    headers.header_received("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT");
+    assert!(headers.is_not_modified(None, Some(last_modified)));
+    assert!(!headers.is_not_modified(None, Some(HttpDate::from_unix_timestamp(784111778))));
+
+    let mut headers = ConditionalHeaders::<4>::new();
+    headers.header_received("If-None-Match", "\"abcdef\"");
+    assert!(!headers.is_not_modified(Some("\"abcdef\""), Some(last_modified)));
+}
+
+#[test]
+fn test_write_not_modified() {
+    use crate::test_utilities::{writer, TestConfig};
+    use crate::traits::ResponseState;
+    use crate::ResponseWriter;
+
+    let mut config = TestConfig::default();
+    let mut response = ResponseState::default();
+    let mut writer = writer(&mut config, &mut response);
+    writer.write_not_modified(
+        Some("Accept-Encoding"),
+        Some("\"a\""),
+        Some(HttpDate::from_unix_timestamp(784111777)),
+    );
+    assert!(!response.close);
+    assert_eq!(
+        core::str::from_utf8(&config.output).unwrap(),
+        "HTTP/1.1 304 Not Modified\r\nVary: Accept-Encoding\r\nETag: \"a\"\r\nLast-Modified: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n"
+    );
+}
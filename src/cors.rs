@@ -0,0 +1,134 @@
+use crate::{HttpMethod, ResponseWriter, WriteResult};
+
+/// Cross-origin resource sharing settings, enabled with `ServerConfig::CORS`.
+///
+/// When this is set, the server answers CORS preflights (`OPTIONS` requests with `Origin` and
+/// `Access-Control-Request-Method` headers) itself. A preflight is only recognized after its headers, so the connection
+/// from `ServerConfig::new_connection` is dropped, and an error from it is ignored. Other `OPTIONS` requests are handled
+/// like any other request. Responses to requests from an allowed `Origin` get the `Access-Control-*` headers,
+/// and every response gets `Vary: Origin`.
+///
+/// ```ignore
+/// const CORS: Option<CorsPolicy> = Some(
+///     CorsPolicy::new(&["https://dashboard.example.com"])
+///         .with_methods(&[HttpMethod::Get, HttpMethod::Post])
+///         .with_headers(&["Content-Type"])
+///         .with_max_age(600),
+/// );
+/// ```
+pub struct CorsPolicy {
+    /// The origins that are allowed, like `https://example.com`. `*` allows every origin.
+    pub allowed_origins: &'static [&'static str],
+    pub allowed_methods: &'static [HttpMethod],
+    /// The request headers that are allowed besides the CORS-safelisted ones.
+    pub allowed_headers: &'static [&'static str],
+    /// How long browsers can cache the preflight response, in seconds.
+    pub max_age: Option<u32>,
+    /// Allow cookies and other credentials. Browsers don't accept this together with the `*` origin, so list the
+    /// origins explicitly when using this.
+    pub allow_credentials: bool,
+}
+
+impl CorsPolicy {
+    /// Allow `allowed_origins` to make `GET`, `HEAD` and `POST` requests.
+    pub const fn new(allowed_origins: &'static [&'static str]) -> Self {
+        Self {
+            allowed_origins,
+            allowed_methods: &[HttpMethod::Get, HttpMethod::Head, HttpMethod::Post],
+            allowed_headers: &[],
+            max_age: None,
+            allow_credentials: false,
+        }
+    }
+
+    pub const fn with_methods(mut self, methods: &'static [HttpMethod]) -> Self {
+        self.allowed_methods = methods;
+        self
+    }
+
+    pub const fn with_headers(mut self, headers: &'static [&'static str]) -> Self {
+        self.allowed_headers = headers;
+        self
+    }
+
+    pub const fn with_max_age(mut self, seconds: u32) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    pub const fn with_credentials(mut self) -> Self {
+        self.allow_credentials = true;
+        self
+    }
+
+    /// The value of `Access-Control-Allow-Origin` for a request from `origin`, or `None` if it is not allowed.
+    pub(crate) fn allowed_origin(&self, origin: &str) -> Option<&'static str> {
+        self.allowed_origins
+            .iter()
+            .find(|&&allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+            .copied()
+    }
+
+    /// Write the CORS headers of a response. `origin` is the allowed origin of the request as returned by
+    /// `allowed_origin`, or `None` if the request has no allowed `Origin`.
+    pub(crate) fn write_headers(
+        &self,
+        writer: &mut dyn ResponseWriter,
+        origin: Option<&str>,
+    ) -> WriteResult {
+        // Responses with and without the CORS headers differ, so caches have to keep them apart. This also applies to
+        // responses without them, or a cached response could be served to an allowed origin.
+        writer.write_response_header("Vary", "Origin")?;
+        match origin {
+            Some(origin) => self.write_allow_origin(writer, origin),
+            None => WriteResult::Ok { bytes_written: 0 },
+        }
+    }
+
+    /// Write the headers that allow `origin` to read the response.
+    fn write_allow_origin(&self, writer: &mut dyn ResponseWriter, origin: &str) -> WriteResult {
+        writer.write_response_header("Access-Control-Allow-Origin", origin)?;
+        if self.allow_credentials {
+            writer.write_response_header("Access-Control-Allow-Credentials", "true")?;
+        }
+        WriteResult::Ok { bytes_written: 0 }
+    }
+
+    /// Write the complete response to a preflight request. `origin` is `None` if the request has no allowed `Origin`,
+    /// the browser then blocks the actual request.
+    pub(crate) fn write_preflight(
+        &self,
+        writer: &mut dyn ResponseWriter,
+        origin: Option<&str>,
+    ) -> WriteResult {
+        writer.write_status(204, "No Content")?;
+        // `Vary: Origin` is already written with the status line
+        if let Some(origin) = origin {
+            self.write_allow_origin(writer, origin)?;
+            write!(writer, "Access-Control-Allow-Methods: ")?;
+            write_list(writer, self.allowed_methods.iter().map(HttpMethod::as_str))?;
+            if !self.allowed_headers.is_empty() {
+                write!(writer, "Access-Control-Allow-Headers: ")?;
+                write_list(writer, self.allowed_headers.iter().copied())?;
+            }
+            if let Some(max_age) = self.max_age {
+                write!(writer, "Access-Control-Max-Age: {max_age}\r\n")?;
+            }
+        }
+        writer.end_headers_start_body()
+    }
+}
+
+/// Write the rest of a header with a comma separated list of `items` as value.
+fn write_list<'a>(
+    writer: &mut dyn ResponseWriter,
+    items: impl Iterator<Item = &'a str>,
+) -> WriteResult {
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            write!(writer, ", ")?;
+        }
+        write!(writer, "{item}")?;
+    }
+    write!(writer, "\r\n")
+}
@@ -0,0 +1,167 @@
+use crate::{Error, Result};
+use heapless::Vec;
+
+/// Resumable parser for `application/x-www-form-urlencoded` request bodies.
+///
+/// Feed this with the bytes from `Connection::body_received`. A field can be split over multiple calls, every time a
+/// field is complete it is decoded and passed to the callback. The key and value of a single field have to fit in
+/// `FIELD_SIZE` bytes, longer fields fail with `Error::FormFieldTooLong`.
+///
+/// The last field is not followed by a `&`, so call `finish` once the body is done (e.g. at the start of
+/// `Connection::poll_write`).
+pub struct FormParser<const FIELD_SIZE: usize> {
+    /// The decoded key, followed by the decoded value
+    buffer: Vec<u8, FIELD_SIZE>,
+    /// The length of the key in `buffer`, or `None` if we have not seen the `=` yet
+    key_len: Option<usize>,
+    escape: Escape,
+}
+
+#[derive(Copy, Clone)]
+enum Escape {
+    None,
+    /// Read a `%`
+    Percent,
+    /// Read a `%` and the first hex digit
+    PercentHex(u8),
+}
+
+impl<const FIELD_SIZE: usize> FormParser<FIELD_SIZE> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            key_len: None,
+            escape: Escape::None,
+        }
+    }
+
+    /// Parse the next part of the body, calling `on_field` with the key and value of every complete field.
+    pub fn feed(&mut self, data: &[u8], mut on_field: impl FnMut(&str, &str) -> Result) -> Result {
+        for &byte in data {
+            self.feed_byte(byte, &mut on_field)?;
+        }
+        Ok(())
+    }
+
+    fn feed_byte(&mut self, byte: u8, on_field: &mut impl FnMut(&str, &str) -> Result) -> Result {
+        match (self.escape, byte) {
+            (Escape::None, b'&') => self.end_field(on_field),
+            (Escape::None, b'=') if self.key_len.is_none() => {
+                self.key_len = Some(self.buffer.len());
+                Ok(())
+            }
+            (Escape::None, b'+') => self.push(b' '),
+            (Escape::None, b'%') => {
+                self.escape = Escape::Percent;
+                Ok(())
+            }
+            (Escape::None, byte) => self.push(byte),
+            (Escape::Percent, byte) if byte.is_ascii_hexdigit() => {
+                self.escape = Escape::PercentHex(byte);
+                Ok(())
+            }
+            (Escape::PercentHex(high), byte) if byte.is_ascii_hexdigit() => {
+                self.escape = Escape::None;
+                self.push(hex_value(high) << 4 | hex_value(byte))
+            }
+            (escape, byte) => {
+                // Not a valid escape, keep the characters as-is like `percent_decode` does
+                self.escape = Escape::None;
+                self.push(b'%')?;
+                if let Escape::PercentHex(high) = escape {
+                    self.push(high)?;
+                }
+                self.feed_byte(byte, on_field)
+            }
+        }
+    }
+
+    /// Call this when the body is done, this passes the last field to `on_field`.
+    pub fn finish(&mut self, mut on_field: impl FnMut(&str, &str) -> Result) -> Result {
+        match core::mem::replace(&mut self.escape, Escape::None) {
+            Escape::None => {}
+            Escape::Percent => self.push(b'%')?,
+            Escape::PercentHex(high) => {
+                self.push(b'%')?;
+                self.push(high)?;
+            }
+        }
+        self.end_field(&mut on_field)
+    }
+
+    fn push(&mut self, byte: u8) -> Result {
+        self.buffer.push(byte).map_err(|_| Error::FormFieldTooLong)
+    }
+
+    fn end_field(&mut self, on_field: &mut impl FnMut(&str, &str) -> Result) -> Result {
+        let key_len = self.key_len.take().unwrap_or(self.buffer.len());
+        let result = if self.buffer.is_empty() {
+            // empty fields, like in `a=1&&b=2`, are skipped
+            Ok(())
+        } else {
+            let (key, value) = self.buffer.split_at(key_len);
+            match (core::str::from_utf8(key), core::str::from_utf8(value)) {
+                (Ok(key), Ok(value)) => on_field(key, value),
+                _ => Err(Error::InvalidUtf8),
+            }
+        };
+        self.buffer.clear();
+        result
+    }
+}
+
+impl<const FIELD_SIZE: usize> Default for FormParser<FIELD_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn hex_value(digit: u8) -> u8 {
+    (digit as char).to_digit(16).unwrap_or_default() as u8
+}
+
+#[test]
+fn test_form_parser() {
+    let body = b"ssid=my+network%21&&channel=6&hidden&name=%e2%9c%93&broken=100%zz%";
+    let mut fields: Vec<(heapless::String<16>, heapless::String<16>), 8> = Vec::new();
+    let mut parser = FormParser::<32>::new();
+    for byte in body.chunks(1) {
+        parser
+            .feed(byte, |key, value| {
+                fields
+                    .push((key.try_into().unwrap(), value.try_into().unwrap()))
+                    .unwrap();
+                Ok(())
+            })
+            .unwrap();
+    }
+    parser
+        .finish(|key, value| {
+            fields
+                .push((key.try_into().unwrap(), value.try_into().unwrap()))
+                .unwrap();
+            Ok(())
+        })
+        .unwrap();
+
+    let fields: Vec<(&str, &str), 8> = fields
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            ("ssid", "my network!"),
+            ("channel", "6"),
+            ("hidden", ""),
+            ("name", "\u{2713}"),
+            ("broken", "100%zz%"),
+        ]
+    );
+
+    let mut parser = FormParser::<4>::new();
+    assert_eq!(
+        parser.feed(b"key=value", |_, _| Ok(())),
+        Err(Error::FormFieldTooLong)
+    );
+}
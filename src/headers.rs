@@ -0,0 +1,190 @@
+use crate::http_utilities::{header_parameters, HeaderParameters};
+use crate::{Error, Result};
+use core::str::FromStr;
+
+macro_rules! header_names {
+    ($($variant:ident => $name:literal,)*) => {
+        /// The name of a header, as passed to `Connection::header_received`.
+        ///
+        /// Header names are case-insensitive, `HeaderName::from("content-length")` is `HeaderName::ContentLength`.
+        /// Headers that are not listed here are kept as `Other`, which also compares case-insensitively.
+        #[derive(Copy, Clone, Debug)]
+        pub enum HeaderName<'a> {
+            $($variant,)*
+            Other(&'a str),
+        }
+
+        impl<'a> HeaderName<'a> {
+            const KNOWN: &'static [(HeaderName<'static>, &'static str)] = &[$((HeaderName::$variant, $name),)*];
+
+            /// The name with the usual capitalization, like `Content-Length`.
+            pub fn as_str(&self) -> &'a str {
+                match self {
+                    $(Self::$variant => $name,)*
+                    Self::Other(name) => name,
+                }
+            }
+        }
+    };
+}
+
+header_names! {
+    Accept => "Accept",
+    AcceptEncoding => "Accept-Encoding",
+    AcceptLanguage => "Accept-Language",
+    AccessControlRequestHeaders => "Access-Control-Request-Headers",
+    AccessControlRequestMethod => "Access-Control-Request-Method",
+    Authorization => "Authorization",
+    CacheControl => "Cache-Control",
+    Connection => "Connection",
+    ContentDisposition => "Content-Disposition",
+    ContentEncoding => "Content-Encoding",
+    ContentLength => "Content-Length",
+    ContentType => "Content-Type",
+    Cookie => "Cookie",
+    Expect => "Expect",
+    Host => "Host",
+    IfModifiedSince => "If-Modified-Since",
+    IfNoneMatch => "If-None-Match",
+    IfRange => "If-Range",
+    Origin => "Origin",
+    Range => "Range",
+    Referer => "Referer",
+    TransferEncoding => "Transfer-Encoding",
+    Upgrade => "Upgrade",
+    UserAgent => "User-Agent",
+}
+
+impl<'a> From<&'a str> for HeaderName<'a> {
+    fn from(name: &'a str) -> Self {
+        Self::KNOWN
+            .iter()
+            .find(|(_, known)| known.eq_ignore_ascii_case(name))
+            .map_or(Self::Other(name), |(header, _)| *header)
+    }
+}
+
+impl PartialEq for HeaderName<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str().eq_ignore_ascii_case(other.as_str())
+    }
+}
+
+impl Eq for HeaderName<'_> {}
+
+/// The value of a `Content-Length` header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ContentLength(pub u64);
+
+impl FromStr for ContentLength {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // `u64::from_str` also accepts a `+`, which is not allowed here
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(Error::InvalidContentLength);
+        }
+        s.parse().map(Self).map_err(|_| Error::InvalidContentLength)
+    }
+}
+
+/// A media type like `text/html; charset=utf-8`, as used in the `Content-Type` header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MediaType<'a> {
+    main_type: &'a str,
+    subtype: &'a str,
+    /// Everything after the first `;`
+    parameters: &'a str,
+}
+
+impl<'a> MediaType<'a> {
+    pub fn parse(value: &'a str) -> Result<Self> {
+        let (essence, parameters) = value.split_once(';').unwrap_or((value, ""));
+        let (main_type, subtype) = essence
+            .trim()
+            .split_once('/')
+            .ok_or(Error::InvalidHttpHeader)?;
+        let is_token = |s: &str| {
+            !s.is_empty()
+                && s.bytes()
+                    .all(|b| b.is_ascii_graphic() && !b"\"(),/:;<=>?@[\\]{}".contains(&b))
+        };
+        if !is_token(main_type) || !is_token(subtype) {
+            return Err(Error::InvalidHttpHeader);
+        }
+        Ok(Self {
+            main_type,
+            subtype,
+            parameters,
+        })
+    }
+
+    /// The part before the `/`, like `text` in `text/html`.
+    pub fn main_type(&self) -> &'a str {
+        self.main_type
+    }
+
+    /// The part after the `/`, like `html` in `text/html`.
+    pub fn subtype(&self) -> &'a str {
+        self.subtype
+    }
+
+    /// Returns `true` if this is `main_type/subtype`, ignoring case and parameters. `subtype` can be `*` to match
+    /// every subtype.
+    pub fn is(&self, main_type: &str, subtype: &str) -> bool {
+        self.main_type.eq_ignore_ascii_case(main_type)
+            && (subtype == "*" || self.subtype.eq_ignore_ascii_case(subtype))
+    }
+
+    /// The `(name, value)` pairs of the parameters, quotes around values are removed.
+    pub fn parameters(&self) -> HeaderParameters<'a> {
+        header_parameters(self.parameters)
+    }
+
+    /// The value of the parameter `name`, which is matched case-insensitively.
+    pub fn parameter(&self, name: &str) -> Option<&'a str> {
+        self.parameters()
+            .find_map(|(key, value)| key.eq_ignore_ascii_case(name).then_some(value))
+    }
+
+    pub fn charset(&self) -> Option<&'a str> {
+        self.parameter("charset")
+    }
+}
+
+#[test]
+fn test_header_name() {
+    assert_eq!(
+        HeaderName::from("content-LENGTH"),
+        HeaderName::ContentLength
+    );
+    assert_eq!(HeaderName::from("Host").as_str(), "Host");
+    assert_eq!(HeaderName::from("x-custom"), HeaderName::Other("X-Custom"));
+    assert_ne!(HeaderName::from("x-custom"), HeaderName::Host);
+
+    assert_eq!("1234".parse(), Ok(ContentLength(1234)));
+    for invalid in ["", "+1", "-1", "1 2", "99999999999999999999"] {
+        assert_eq!(
+            invalid.parse::<ContentLength>(),
+            Err(Error::InvalidContentLength)
+        );
+    }
+}
+
+#[test]
+fn test_media_type() {
+    let media_type =
+        MediaType::parse("Multipart/Form-Data; charset=utf-8; boundary=\"a b\"").unwrap();
+    assert_eq!(media_type.main_type(), "Multipart");
+    assert_eq!(media_type.subtype(), "Form-Data");
+    assert!(media_type.is("multipart", "form-data"));
+    assert!(media_type.is("multipart", "*"));
+    assert!(!media_type.is("text", "*"));
+    assert_eq!(media_type.parameter("BOUNDARY"), Some("a b"));
+    assert_eq!(media_type.charset(), Some("utf-8"));
+    assert_eq!(media_type.parameters().count(), 2);
+
+    for invalid in ["", "text", "text/", "/html", "text/ht ml", "te(x)t/html"] {
+        assert_eq!(MediaType::parse(invalid), Err(Error::InvalidHttpHeader));
+    }
+}
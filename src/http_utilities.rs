@@ -1,15 +1,14 @@
 use crate::{Error, Result};
-use core::{ascii::Char as AsciiChar, str::FromStr};
+use core::str::FromStr;
 
-pub fn read_http_request_line(data: &[u8]) -> Result<(HttpMethod, &str, &[u8])> {
-    let (line, remaining) =
-        read_ascii_until(data, AsciiChar::LineFeed).ok_or(Error::InvalidRequestLine)?;
+/// Parse a single request line. `data` should contain exactly one line, including the trailing `\r\n` or `\n`.
+pub fn read_http_request_line(data: &[u8]) -> Result<(HttpMethod, &str, HttpVersion)> {
+    let line = read_line(data).ok_or(Error::InvalidRequestLine)?;
     let (method, line) = line.split_once(' ').ok_or(Error::InvalidRequestLine)?;
     let method = HttpMethod::from_str(method)?;
 
-    let (url, _http_version) = line.rsplit_once(' ').ok_or(Error::InvalidRequestLine)?;
-    // TODO: validate http version?
-    Ok((method, url, remaining))
+    let (url, http_version) = line.rsplit_once(' ').ok_or(Error::InvalidRequestLine)?;
+    Ok((method, url, HttpVersion::from_str(http_version)?))
 }
 
 #[test]
@@ -21,55 +20,96 @@ fn test_read_http_request_line() {
     );
     assert_eq!(
         read_http_request_line(&b"GET /foo/bar HTTP/1.1\n"[..]).unwrap(),
-        (HttpMethod::Get, "/foo/bar", &[][..])
+        (HttpMethod::Get, "/foo/bar", HttpVersion::Http11)
     );
     assert_eq!(
         read_http_request_line(&b"GET this is a weird url HTTP/1.1\n"[..]).unwrap(),
-        (HttpMethod::Get, "this is a weird url", &[][..])
+        (HttpMethod::Get, "this is a weird url", HttpVersion::Http11)
+    );
+    assert_eq!(
+        read_http_request_line(&b"GET / HTTP/1.0\r\n"[..]).unwrap(),
+        (HttpMethod::Get, "/", HttpVersion::Http10)
+    );
+    assert_eq!(
+        read_http_request_line(&b"GET / HTTP/1.2\r\n"[..]).unwrap(),
+        (HttpMethod::Get, "/", HttpVersion::Unknown)
+    );
+    assert_eq!(
+        read_http_request_line(&b"GET / HTTP/2.0\r\n"[..]),
+        Err(Error::HttpVersionNotSupported)
+    );
+    assert_eq!(
+        read_http_request_line(&b"GET / http/1.1\r\n"[..]),
+        Err(Error::InvalidHttpVersion)
+    );
+    assert_eq!(
+        read_http_request_line(&b"GET / HTTP/1.10\r\n"[..]),
+        Err(Error::InvalidHttpVersion)
     );
 }
 
-pub fn read_header_line(data: &[u8]) -> HeaderLine {
-    if let Some(remaining) = data.strip_prefix(b"\r\n") {
-        return HeaderLine::Whiteline { remaining };
-    }
-
-    let Some((key, remaining)) = read_ascii_until(data, AsciiChar::Colon) else {
+/// Parse a single header line. `data` should contain exactly one line, including the trailing `\r\n` or `\n`.
+pub fn read_header_line(data: &[u8]) -> HeaderLine<'_> {
+    let Some(line) = read_line(data) else {
         return HeaderLine::Error(Error::InvalidHttpHeader);
     };
-    let Some((value, remaining)) = read_ascii_until(remaining, AsciiChar::LineFeed) else {
+    if line.is_empty() {
+        return HeaderLine::Whiteline;
+    }
+
+    let Some((key, value)) = line.split_once(':') else {
         return HeaderLine::Error(Error::InvalidHttpHeader);
     };
     HeaderLine::Header {
         key: key.trim(),
         value: value.trim(),
-        remaining,
     }
 }
 
+#[test]
+fn test_read_header_line() {
+    assert!(matches!(read_header_line(b"\r\n"), HeaderLine::Whiteline));
+    assert!(matches!(read_header_line(b"\n"), HeaderLine::Whiteline));
+    assert!(matches!(
+        read_header_line(b"Host: localhost\r\n"),
+        HeaderLine::Header {
+            key: "Host",
+            value: "localhost"
+        }
+    ));
+    assert!(matches!(
+        read_header_line(b"Host: localhost\n"),
+        HeaderLine::Header {
+            key: "Host",
+            value: "localhost"
+        }
+    ));
+    assert!(matches!(
+        read_header_line(b"Host localhost\r\n"),
+        HeaderLine::Error(Error::InvalidHttpHeader)
+    ));
+}
+
 pub enum HeaderLine<'a> {
     Error(super::Error),
-    Whiteline {
-        remaining: &'a [u8],
-    },
-    Header {
-        key: &'a str,
-        value: &'a str,
-        remaining: &'a [u8],
-    },
-}
-
-fn read_ascii_until(data: &[u8], char: AsciiChar) -> Option<(&str, &[u8])> {
-    for (i, b) in data.iter().enumerate() {
-        let c = AsciiChar::from_u8(*b)?;
-        if c == char {
-            return Some((
-                unsafe { core::str::from_utf8_unchecked(&data[..i]) }, // safe because it's all ascii characters
-                &data[i + 1..],
-            ));
-        }
+    Whiteline,
+    Header { key: &'a str, value: &'a str },
+}
+
+/// Get the content of a single line, without the `\r\n` or `\n` at the end.
+///
+/// Returns `None` if `data` is not a single line of ascii characters.
+fn read_line(data: &[u8]) -> Option<&str> {
+    let line = data.strip_suffix(b"\n")?;
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    // a lone `\r` in the middle of a line is not allowed
+    if !line
+        .iter()
+        .all(|b| b.is_ascii() && *b != b'\r' && *b != b'\n')
+    {
+        return None;
     }
-    None
+    Some(unsafe { core::str::from_utf8_unchecked(line) }) // safe because it's all ascii characters
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
@@ -85,6 +125,22 @@ pub enum HttpMethod {
     Patch,
 }
 
+impl HttpMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Connect => "CONNECT",
+            Self::Options => "OPTIONS",
+            Self::Trace => "TRACE",
+            Self::Patch => "PATCH",
+        }
+    }
+}
+
 impl FromStr for HttpMethod {
     type Err = Error;
 
@@ -103,3 +159,398 @@ impl FromStr for HttpMethod {
         }
     }
 }
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+    /// A newer HTTP/1.x version, which is handled as HTTP/1.1
+    Unknown,
+}
+
+impl HttpVersion {
+    /// The version that the server responds with
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http10 => "HTTP/1.0",
+            Self::Http11 | Self::Unknown => "HTTP/1.1",
+        }
+    }
+}
+
+impl FromStr for HttpVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.as_bytes() {
+            b"HTTP/1.0" => Ok(Self::Http10),
+            b"HTTP/1.1" => Ok(Self::Http11),
+            [b'H', b'T', b'T', b'P', b'/', b'1', b'.', minor] if minor.is_ascii_digit() => {
+                Ok(Self::Unknown)
+            }
+            [b'H', b'T', b'T', b'P', b'/', major, b'.', minor]
+                if major.is_ascii_digit() && minor.is_ascii_digit() =>
+            {
+                Err(Error::HttpVersionNotSupported)
+            }
+            _ => Err(Error::InvalidHttpVersion),
+        }
+    }
+}
+
+/// The request target of a request, split into its components.
+///
+/// This borrows from the path given to `ServerConfig::new_connection`, the components are not decoded. Use
+/// `percent_decode` or `Uri::decoded_path` to get the decoded value.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Uri<'a> {
+    scheme: Option<&'a str>,
+    authority: Option<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+}
+
+impl<'a> Uri<'a> {
+    /// Parse a request target. This can be a path (`/index.html?foo=bar`), an absolute url
+    /// (`http://example.com/index.html`), an authority (`example.com:443`, used by `CONNECT`) or `*` (used by
+    /// `OPTIONS`).
+    pub fn parse(target: &'a str) -> Result<Self> {
+        if target.is_empty() || target.contains(|c: char| c.is_ascii_whitespace()) {
+            return Err(Error::InvalidUri);
+        }
+        let (target, fragment) = match target.split_once('#') {
+            Some((target, fragment)) => (target, Some(fragment)),
+            None => (target, None),
+        };
+        let (target, query) = match target.split_once('?') {
+            Some((target, query)) => (target, Some(query)),
+            None => (target, None),
+        };
+
+        let mut uri = Self {
+            scheme: None,
+            authority: None,
+            path: target,
+            query,
+            fragment,
+        };
+        if target.starts_with('/') || target == "*" {
+            return Ok(uri);
+        }
+        if let Some((scheme, rest)) = target.split_once("://") {
+            if scheme.is_empty()
+                || !scheme
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b))
+            {
+                return Err(Error::InvalidUri);
+            }
+            let (authority, path) = rest.find('/').map_or((rest, "/"), |i| rest.split_at(i));
+            uri.scheme = Some(scheme);
+            uri.authority = Some(authority);
+            uri.path = path;
+        } else if query.is_none() && fragment.is_none() {
+            uri.authority = Some(target);
+            uri.path = "";
+        } else {
+            return Err(Error::InvalidUri);
+        }
+        Ok(uri)
+    }
+
+    /// The scheme of an absolute url, e.g. `http`
+    pub fn scheme(&self) -> Option<&'a str> {
+        self.scheme
+    }
+
+    /// The host and port of an absolute url or `CONNECT` request, e.g. `example.com:8080`
+    pub fn authority(&self) -> Option<&'a str> {
+        self.authority
+    }
+
+    /// The path, e.g. `/index.html`. This is empty for `CONNECT` requests.
+    pub fn path(&self) -> &'a str {
+        self.path
+    }
+
+    /// The part after the `?`, if any
+    pub fn query(&self) -> Option<&'a str> {
+        self.query
+    }
+
+    /// The part after the `#`, if any. Browsers don't send this, but other clients might.
+    pub fn fragment(&self) -> Option<&'a str> {
+        self.fragment
+    }
+
+    /// The path with percent-encoded characters decoded
+    pub fn decoded_path(&self) -> PercentDecode<'a> {
+        percent_decode(self.path)
+    }
+
+    /// The decoded `key=value` pairs of the query. See `query_pairs`.
+    pub fn query_pairs(&self) -> QueryPairs<'a> {
+        query_pairs(self.query.unwrap_or(""))
+    }
+
+    /// The decoded value of the first `key` in the query. See `query_value`.
+    pub fn query_value(&self, key: &str) -> Option<PercentDecode<'a>> {
+        query_value(self.query.unwrap_or(""), key)
+    }
+}
+
+#[test]
+fn test_uri() {
+    let uri = Uri::parse("/foo/bar?a=b&c#top").unwrap();
+    assert_eq!(uri.scheme(), None);
+    assert_eq!(uri.authority(), None);
+    assert_eq!(uri.path(), "/foo/bar");
+    assert_eq!(uri.query(), Some("a=b&c"));
+    assert_eq!(uri.fragment(), Some("top"));
+
+    let uri = Uri::parse("http://example.com:8080/index.html?x").unwrap();
+    assert_eq!(uri.scheme(), Some("http"));
+    assert_eq!(uri.authority(), Some("example.com:8080"));
+    assert_eq!(uri.path(), "/index.html");
+    assert_eq!(uri.query(), Some("x"));
+
+    let uri = Uri::parse("http://example.com").unwrap();
+    assert_eq!(uri.path(), "/");
+
+    let uri = Uri::parse("example.com:443").unwrap();
+    assert_eq!(uri.authority(), Some("example.com:443"));
+    assert_eq!(uri.path(), "");
+
+    assert_eq!(Uri::parse("*").unwrap().path(), "*");
+    assert_eq!(Uri::parse(""), Err(Error::InvalidUri));
+    assert_eq!(Uri::parse("foo?bar"), Err(Error::InvalidUri));
+    assert_eq!(Uri::parse("/foo bar"), Err(Error::InvalidUri));
+}
+
+/// Percent-decode `s`, e.g. `hello%20world` becomes `hello world`.
+///
+/// Invalid escapes like `%zz` are passed through as-is.
+pub fn percent_decode(s: &str) -> PercentDecode<'_> {
+    PercentDecode {
+        bytes: s.as_bytes().iter(),
+        plus_as_space: false,
+    }
+}
+
+/// Like `percent_decode`, but also decodes `+` as a space, which is used in query strings and form data.
+pub fn form_decode(s: &str) -> PercentDecode<'_> {
+    PercentDecode {
+        bytes: s.as_bytes().iter(),
+        plus_as_space: true,
+    }
+}
+
+/// Iterator over the bytes of a percent-encoded string. Created by `percent_decode` or `form_decode`.
+#[derive(Clone, Debug)]
+pub struct PercentDecode<'a> {
+    bytes: core::slice::Iter<'a, u8>,
+    plus_as_space: bool,
+}
+
+impl<'a> PercentDecode<'a> {
+    /// Decode into `buffer`, returning the decoded string.
+    ///
+    /// Fails with `Error::BufferTooSmall` if the decoded value does not fit, or `Error::InvalidUtf8` if the decoded
+    /// value is not valid UTF-8.
+    pub fn decode_into(self, buffer: &mut [u8]) -> Result<&str> {
+        let mut len = 0;
+        for byte in self {
+            *buffer.get_mut(len).ok_or(Error::BufferTooSmall)? = byte;
+            len += 1;
+        }
+        core::str::from_utf8(&buffer[..len]).map_err(|_| Error::InvalidUtf8)
+    }
+}
+
+impl<'a> Iterator for PercentDecode<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = *self.bytes.next()?;
+        if byte == b'+' && self.plus_as_space {
+            return Some(b' ');
+        }
+        if byte == b'%' {
+            let rest = self.bytes.as_slice();
+            if let Some(decoded) = rest.get(..2).and_then(decode_hex_byte) {
+                self.bytes.nth(1);
+                return Some(decoded);
+            }
+        }
+        Some(byte)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.bytes.len();
+        (len.div_ceil(3), Some(len))
+    }
+}
+
+impl PartialEq<str> for PercentDecode<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.clone().eq(other.bytes())
+    }
+}
+
+impl PartialEq<&str> for PercentDecode<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+fn decode_hex_byte(hex: &[u8]) -> Option<u8> {
+    let hex = core::str::from_utf8(hex).ok()?;
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u8::from_str_radix(hex, 16).ok()
+}
+
+#[test]
+fn test_percent_decode() {
+    let mut buffer = [0u8; 16];
+    assert_eq!(
+        percent_decode("hello%20world").decode_into(&mut buffer),
+        Ok("hello world")
+    );
+    assert_eq!(
+        percent_decode("%e2%9c%93%2").decode_into(&mut buffer),
+        Ok("\u{2713}%2")
+    );
+    assert_eq!(
+        percent_decode("100%zz").decode_into(&mut buffer),
+        Ok("100%zz")
+    );
+    assert_eq!(
+        percent_decode("%ff").decode_into(&mut buffer),
+        Err(Error::InvalidUtf8)
+    );
+    assert_eq!(
+        percent_decode("this is way too long").decode_into(&mut buffer),
+        Err(Error::BufferTooSmall)
+    );
+}
+
+/// Iterate over the `key=value` pairs of a query string like `ssid=my+network&channel=6`.
+///
+/// Keys and values are decoded lazily with `form_decode`. A key without `=` has an empty value, empty pairs are
+/// skipped.
+pub fn query_pairs(query: &str) -> QueryPairs<'_> {
+    QueryPairs {
+        pairs: query.split('&'),
+    }
+}
+
+/// Get the decoded value of the first `key` in a query string like `ssid=my+network&channel=6`.
+///
+/// `key` is compared to the decoded keys in the query string, so `key` should not be encoded.
+pub fn query_value<'a>(query: &'a str, key: &str) -> Option<PercentDecode<'a>> {
+    query_pairs(query).find_map(|(k, v)| (k == key).then_some(v))
+}
+
+/// Iterator over the decoded pairs of a query string. Created by `query_pairs`.
+#[derive(Clone, Debug)]
+pub struct QueryPairs<'a> {
+    pairs: core::str::Split<'a, char>,
+}
+
+impl<'a> Iterator for QueryPairs<'a> {
+    type Item = (PercentDecode<'a>, PercentDecode<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pair = self.pairs.find(|pair| !pair.is_empty())?;
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        Some((form_decode(key), form_decode(value)))
+    }
+}
+
+#[test]
+fn test_query_pairs() {
+    let uri = Uri::parse("/config?ssid=my+network%21&&channel=6&hidden").unwrap();
+    let mut pairs = uri.query_pairs();
+    let (key, value) = pairs.next().unwrap();
+    assert!(key == "ssid" && value == "my network!");
+    let (key, value) = pairs.next().unwrap();
+    assert!(key == "channel" && value == "6");
+    let (key, value) = pairs.next().unwrap();
+    assert!(key == "hidden" && value == "");
+    assert!(pairs.next().is_none());
+
+    let mut buffer = [0u8; 16];
+    assert_eq!(
+        uri.query_value("ssid").unwrap().decode_into(&mut buffer),
+        Ok("my network!")
+    );
+    assert!(query_value("a%5Bb%5D=1", "a[b]").unwrap() == "1");
+    assert!(query_value("a=1", "b").is_none());
+    assert!(Uri::parse("/").unwrap().query_pairs().next().is_none());
+}
+
+/// Iterate over the parameters of a header value, like the `name="file"; filename="a.txt"` in
+/// `Content-Disposition: form-data; name="file"; filename="a.txt"`. `s` should be the part after the first `;`.
+///
+/// Quotes around values are removed, escaped characters inside of quotes are returned as-is.
+pub(crate) fn header_parameters(s: &str) -> HeaderParameters<'_> {
+    HeaderParameters { rest: s }
+}
+
+/// Iterator over the `(name, value)` pairs of the parameters in a header value, see `MediaType::parameters`.
+#[derive(Clone, Debug)]
+pub struct HeaderParameters<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for HeaderParameters<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self
+            .rest
+            .trim_start_matches(|c: char| c == ';' || c.is_ascii_whitespace());
+        if rest.is_empty() {
+            return None;
+        }
+        let (key, rest) = rest.split_at(rest.find(['=', ';']).unwrap_or(rest.len()));
+        let Some(rest) = rest.strip_prefix('=') else {
+            self.rest = rest;
+            return Some((key.trim(), ""));
+        };
+
+        let rest = rest.trim_start();
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let mut escaped = false;
+            let end = quoted
+                .char_indices()
+                .find(|&(_, c)| {
+                    let is_end = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    is_end
+                })
+                .map_or(quoted.len(), |(i, _)| i);
+            self.rest = quoted.get(end + 1..).unwrap_or("");
+            &quoted[..end]
+        } else {
+            let end = rest.find(';').unwrap_or(rest.len());
+            self.rest = &rest[end..];
+            rest[..end].trim()
+        };
+        Some((key.trim(), value))
+    }
+}
+
+#[test]
+fn test_header_parameters() {
+    let mut parameters =
+        header_parameters(r#" name="a \"b\"; c"; filename=file.txt;;flag ; x = y"#);
+    assert_eq!(parameters.next(), Some(("name", r#"a \"b\"; c"#)));
+    assert_eq!(parameters.next(), Some(("filename", "file.txt")));
+    assert_eq!(parameters.next(), Some(("flag", "")));
+    assert_eq!(parameters.next(), Some(("x", "y")));
+    assert_eq!(parameters.next(), None);
+}
@@ -1,35 +1,85 @@
 #![no_std]
-// should be stable rust 1.77, march 21th 2024
-#![feature(ip_in_core)]
-// Useful tools for dealing with ascii characters
-// This one is stable but the API might change
-// tracking issue: https://github.com/rust-lang/rust/issues/110998
-#![feature(ascii_char, ascii_char_variants)]
 // Custom try implementation, it's neat
 // Seems to be mostly stable
 // tracking issue: https://github.com/rust-lang/rust/issues/84277
 #![feature(try_trait_v2)]
 
+mod assets;
+mod chunked;
 mod client;
+mod conditional;
+mod cors;
+mod form;
+mod headers;
 mod http_utilities;
+mod multipart;
+mod range;
+mod router;
 mod traits;
 
-use client::{Client, ClientState};
+#[cfg(test)]
+mod test_utilities;
+
+use client::{Client, ClientState, TimeoutPhase};
 use heapless::Vec;
 
+pub use assets::{Asset, AssetConnection, StaticAssets};
+pub use conditional::{ConditionalHeaders, HttpDate};
 pub use core::net::SocketAddr;
-pub use http_utilities::HttpMethod;
-pub use traits::{Connection, ReadResult, ResponseWriter, ServerConfig, WriteResult};
+pub use cors::CorsPolicy;
+pub use form::FormParser;
+pub use headers::{ContentLength, HeaderName, MediaType};
+pub use http_utilities::{
+    form_decode, percent_decode, query_pairs, query_value, HeaderParameters, HttpMethod,
+    HttpVersion, PercentDecode, QueryPairs, Uri,
+};
+pub use multipart::{MultipartEvent, MultipartParser, PartHeaders};
+pub use range::{RangeRequest, RangeResponse};
+pub use router::{AllowedMethods, PathParameters, Route, RouteRequest, Routed, Router};
+pub use traits::{
+    Connection, EvictionPolicy, ReadResult, ResponseWriter, ServerConfig, ServerLimits,
+    ServerTimeouts, WriteResult,
+};
 
-pub struct Server<T: ServerConfig, const NUMBER_OF_CONNECTIONS: usize> {
+/// An HTTP server handling up to `NUMBER_OF_CONNECTIONS` clients at the same time.
+///
+/// Every client gets a buffer of `BUFFER_SIZE` bytes to hold request lines and headers that are split over multiple
+/// `data_received` calls. This also limits the maximum length of a single request line or header line.
+///
+/// Every client also gets a TX buffer of `TX_BUFFER_SIZE` bytes for the part of a response that `ServerConfig::write`
+/// did not accept. A single `Connection::poll_write` can't write more than this while the transport is blocked, see
+/// `ResponseWriter::write_capacity`. The default fits the headers and one slice of a default `AssetConnection`.
+///
+/// Requests that are pipelined behind the one that is being answered wait in a buffer of `PIPELINE_BUFFER_SIZE` bytes.
+/// If they don't fit, the connection is closed after the current response, and the client sends them again.
+pub struct Server<
+    T: ServerConfig,
+    const NUMBER_OF_CONNECTIONS: usize,
+    const BUFFER_SIZE: usize = 256,
+    const TX_BUFFER_SIZE: usize = 1536,
+    const PIPELINE_BUFFER_SIZE: usize = 1024,
+> {
     pub inner: T,
-    clients: Vec<Client<T::ConnectionType>, NUMBER_OF_CONNECTIONS>,
+    clients: Vec<
+        Client<T::ConnectionType, BUFFER_SIZE, TX_BUFFER_SIZE, PIPELINE_BUFFER_SIZE>,
+        NUMBER_OF_CONNECTIONS,
+    >,
+    /// Counts up on every event, clients store the value of their last activity to find the oldest for eviction.
+    activity: u64,
 }
-impl<T: ServerConfig, const NUMBER_OF_CONNECTIONS: usize> Server<T, NUMBER_OF_CONNECTIONS> {
+impl<
+        T: ServerConfig,
+        const NUMBER_OF_CONNECTIONS: usize,
+        const BUFFER_SIZE: usize,
+        const TX_BUFFER_SIZE: usize,
+        const PIPELINE_BUFFER_SIZE: usize,
+    > Server<T, NUMBER_OF_CONNECTIONS, BUFFER_SIZE, TX_BUFFER_SIZE, PIPELINE_BUFFER_SIZE>
+{
     pub fn new(config: T) -> Self {
         Self {
             inner: config,
             clients: Vec::new(),
+            activity: 0,
         }
     }
 
@@ -40,57 +90,244 @@ impl<T: ServerConfig, const NUMBER_OF_CONNECTIONS: usize> Server<T, NUMBER_OF_CO
             }
             self.clients.remove(idx);
         }
-        if self.clients.push(Client::new(addr)).is_err() {
+        if self.clients.is_full() {
+            if let Some(idx) = self.eviction_candidate() {
+                // `remove` keeps the order in which `poll_write` serves the clients
+                let evicted = self.clients.remove(idx).address;
+                self.inner.connection_evicted(evicted);
+            }
+        }
+        let mut client = Client::new(addr);
+        client.last_activity = self.next_activity();
+        if self.clients.push(client).is_err() {
+            self.reject(addr, HttpVersion::Http11, &Error::TooManyConnections);
             Err(Error::TooManyConnections)
         } else {
             Ok(())
         }
     }
 
+    /// The index of the client that is closed for a new connection according to `ServerConfig::EVICTION`.
+    fn eviction_candidate(&self) -> Option<usize> {
+        let is_candidate =
+            |client: &Client<_, BUFFER_SIZE, TX_BUFFER_SIZE, PIPELINE_BUFFER_SIZE>| {
+                match T::EVICTION {
+                    EvictionPolicy::Reject => false,
+                    EvictionPolicy::OldestIdle => client.is_idle() && client.requests_handled > 0,
+                    EvictionPolicy::OldestReadingRequestLine => {
+                        client.state == ClientState::ReadingRequestLine
+                    }
+                }
+            };
+        self.clients
+            .iter()
+            .enumerate()
+            .filter(|(_, client)| is_candidate(client))
+            .min_by_key(|(_, client)| client.last_activity)
+            .map(|(idx, _)| idx)
+    }
+
+    fn next_activity(&mut self) -> u64 {
+        self.activity += 1;
+        self.activity
+    }
+
     pub fn client_disconnected(&mut self, addr: SocketAddr) {
         self.clients.retain(|c| c.address != addr);
     }
 
+    /// Process incoming data from `addr`.
+    ///
+    /// If the request is invalid, an error response is sent (see `ServerConfig::SEND_ERROR_RESPONSES`) and the
+    /// connection is closed.
     pub fn data_received(&mut self, addr: SocketAddr, data: &[u8]) -> Result {
-        if let Some(connection) = self.clients.iter_mut().find(|c| c.address == addr) {
-            connection.data_received(&mut self.inner, data)
+        let activity = self.next_activity();
+        let Some(idx) = self.clients.iter().position(|c| c.address == addr) else {
+            return Err(Error::TooManyConnections);
+        };
+        let client = &mut self.clients[idx];
+        client.last_activity = activity;
+        let result = client.data_received(&mut self.inner, data);
+        if let Err(e) = &result {
+            self.fail(idx, e);
+            self.clients.retain(|c| c.state != ClientState::Done);
+        }
+        result
+    }
+
+    /// Close the client at `idx` because of `error`. The client gets an error response, unless its response has
+    /// already started.
+    fn fail(&mut self, idx: usize, error: &Error) {
+        let client = &mut self.clients[idx];
+        // If we're already writing a response, we can't start a new one
+        let response_started = client.state >= ClientState::Writing;
+        client.state = ClientState::Done;
+        let (addr, version) = (client.address, client.version());
+        if response_started {
+            self.inner.close_connection(addr);
         } else {
-            Err(Error::TooManyConnections)
+            self.reject(addr, version, error);
         }
     }
 
+    /// Send an error response for `error` to `addr` (if enabled) and close the connection.
+    fn reject(&mut self, addr: SocketAddr, version: HttpVersion, error: &Error) {
+        if T::SEND_ERROR_RESPONSES {
+            if let Some((code, reason)) = error.status() {
+                // the connection is closed anyway, so there is nothing to do if this fails
+                let _ = traits::write_error_response(
+                    &mut self.inner,
+                    addr,
+                    version,
+                    code,
+                    reason,
+                    error,
+                );
+            }
+        }
+        self.inner.close_connection(addr);
+    }
+
+    /// Enforce `ServerConfig::TIMEOUTS`. Call this regularly with the current time of a monotonic clock, e.g. the
+    /// milliseconds since boot.
+    ///
+    /// The time of activity on a connection is recorded by the first `tick` after it, so timeouts are accurate to the
+    /// interval between calls. Connections that timed out are closed with `ServerConfig::close_connection`, and their
+    /// addresses are returned.
+    pub fn tick(&mut self, now: u64) -> Vec<SocketAddr, NUMBER_OF_CONNECTIONS> {
+        let mut closed = Vec::new();
+        for i in 0..self.clients.len() {
+            let client = &mut self.clients[i];
+            let Some(phase) = client.tick(now, &T::TIMEOUTS) else {
+                continue;
+            };
+            client.state = ClientState::Done;
+            let (addr, version) = (client.address, client.version());
+            match phase {
+                TimeoutPhase::Headers | TimeoutPhase::Body => {
+                    self.reject(addr, version, &Error::RequestTimeout)
+                }
+                TimeoutPhase::Idle | TimeoutPhase::Writing => self.inner.close_connection(addr),
+            }
+            // there is at most one entry for every client
+            let _ = closed.push(addr);
+        }
+        self.clients.retain(|c| c.state != ClientState::Done);
+        closed
+    }
+
+    /// Returns `true` if there are responses that are not completely written yet.
+    ///
+    /// A `Connection` can write its response over multiple `poll_write` calls, so keep calling `poll_write` while this
+    /// returns `true`.
+    pub fn is_writing(&self) -> bool {
+        self.clients.iter().any(|c| c.state == ClientState::Writing)
+    }
+
     pub fn poll_write(&mut self) -> Result {
-        for client in &mut self.clients {
-            if client.state == ClientState::Writing {
+        let mut result = Ok(());
+        for idx in 0..self.clients.len() {
+            // A pipelined request may be ready as soon as the previous response is done
+            while self.clients[idx].state == ClientState::Writing {
+                let client = &mut self.clients[idx];
                 match client.poll_write(&mut self.inner) {
-                    WriteResult::Ok { .. } => {}
+                    // the connection or the transport needs another `poll_write` to continue
+                    WriteResult::Ok { .. } | WriteResult::WouldBlock => break,
                     WriteResult::Err(e) => {
-                        return Err(e);
+                        // part of the response may be lost, so the connection can't be used anymore
+                        self.fail(idx, &e);
+                        result = Err(e);
                     }
                     WriteResult::EndWriting => {
-                        client.state = ClientState::Done;
-                        if let Some(connection) = &mut client.connection {
-                            self.inner
-                                .on_client_done_writing(client.address, connection);
+                        // a keep-alive connection is idle from here
+                        let activity = self.next_activity();
+                        let client = &mut self.clients[idx];
+                        client.last_activity = activity;
+                        // an invalid pipelined request is rejected like any other
+                        if let Err(e) = client.finish_response(&mut self.inner) {
+                            self.fail(idx, &e);
+                            result = Err(e);
                         }
                     }
                 }
             }
         }
         self.clients.retain(|c| c.state != ClientState::Done);
-        Ok(())
+        result
     }
 }
 
 pub type Result<T = ()> = core::result::Result<T, Error>;
 
+impl Error {
+    /// The status code and reason phrase of the response that is sent to the client when this error occurs.
+    ///
+    /// Returns `None` for errors that are not caused by a request.
+    pub fn status(&self) -> Option<(u16, &'static str)> {
+        match self {
+            Self::InvalidRequestLine
+            | Self::InvalidHttpVersion
+            | Self::InvalidUri
+            | Self::InvalidUtf8
+            | Self::InvalidHttpHeader
+            | Self::InvalidContentLength
+            | Self::InvalidTransferEncoding
+            | Self::InvalidChunk
+            | Self::InvalidMultipart => Some((400, "Bad Request")),
+            Self::MethodNotAllowed => Some((405, "Method Not Allowed")),
+            Self::RequestTimeout => Some((408, "Request Timeout")),
+            Self::FormFieldTooLong | Self::MultipartHeaderTooLong | Self::BodyTooLong => {
+                Some((413, "Content Too Large"))
+            }
+            Self::RequestLineTooLong => Some((414, "URI Too Long")),
+            Self::HttpHeaderTooLong | Self::TooManyHeaders => {
+                Some((431, "Request Header Fields Too Large"))
+            }
+            Self::InvalidClientState | Self::BufferTooSmall => Some((500, "Internal Server Error")),
+            // RFC 9110 section 9.1: an unknown method is responded to with 501, not 405
+            Self::InvalidHttpMethod => Some((501, "Not Implemented")),
+            Self::TooManyConnections => Some((503, "Service Unavailable")),
+            Self::HttpVersionNotSupported => Some((505, "HTTP Version Not Supported")),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 pub enum Error {
     TooManyConnections,
     InvalidRequestLine,
+    RequestLineTooLong,
     InvalidHttpMethod,
+    InvalidHttpVersion,
+    HttpVersionNotSupported,
+    InvalidUri,
+    InvalidUtf8,
+    /// A date is not in the format of RFC 9110 section 5.6.7
+    InvalidHttpDate,
+    /// A buffer given to a decoding function was not large enough
+    BufferTooSmall,
+    /// A field in a form body did not fit in the `FormParser`
+    FormFieldTooLong,
+    InvalidMultipart,
+    /// The headers of a part did not fit in the `MultipartParser`
+    MultipartHeaderTooLong,
+    /// Can be returned from `ServerConfig::new_connection` when the path exists, but not for this method.
+    MethodNotAllowed,
     InvalidHttpHeader,
+    HttpHeaderTooLong,
+    /// The request has more headers than `ServerLimits::max_headers`
+    TooManyHeaders,
+    /// The request body is larger than `ServerLimits::max_body_size`
+    BodyTooLong,
+    /// The client took longer than `ServerConfig::TIMEOUTS` allows
+    RequestTimeout,
+    /// A response wrote more than the TX buffer of the `Server` can hold while the transport was not accepting data
+    TxBufferFull,
+    InvalidContentLength,
+    InvalidTransferEncoding,
+    InvalidChunk,
     AlreadyConnected,
     InvalidClientState,
     ClientNotFound,
@@ -98,3 +335,510 @@ pub enum Error {
     #[deprecated(note = "only used for development")]
     Unimplemented,
 }
+
+#[test]
+fn test_keep_alive() {
+    use test_utilities::{TestConfig, ADDR};
+
+    let mut server = Server::<TestConfig, 1>::new(TestConfig::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(ADDR, b"GET /first HTTP/1.1\r\n\r\n")
+        .unwrap();
+    server.poll_write().unwrap();
+    assert!(!server.inner.closed);
+
+    server
+        .data_received(ADDR, b"GET /second HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    server.poll_write().unwrap();
+    assert!(server.inner.closed);
+    assert!(server.clients.is_empty());
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\n/first\
+         HTTP/1.1 200 OK\r\nContent-Length: 7\r\nConnection: close\r\n\r\n/second"
+    );
+}
+
+#[test]
+fn test_pipelining() {
+    use test_utilities::{TestConfig, ADDR};
+
+    let mut server = Server::<TestConfig, 1>::new(TestConfig::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(
+            ADDR,
+            b"POST /first HTTP/1.1\r\nContent-Length: 4\r\n\r\nbodyGET /second HTTP/1.1\r\n\r\nGET /th",
+        )
+        .unwrap();
+    server.data_received(ADDR, b"ird HTTP/1.1\r\n\r\n").unwrap();
+    server.poll_write().unwrap();
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\n/first\
+         HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\n/second\
+         HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\n/third"
+    );
+    assert_eq!(server.clients[0].state, ClientState::ReadingRequestLine);
+
+    // an empty line after a body is ignored
+    let mut server = Server::<TestConfig, 1>::new(TestConfig::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(
+            ADDR,
+            b"POST /first HTTP/1.1\r\nContent-Length: 2\r\n\r\nab\r\nGET /second HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+    server.poll_write().unwrap();
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\n/first\
+         HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\n/second"
+    );
+
+    // pipelined requests that don't fit in the pipeline buffer close the connection after the current response
+    let mut server = Server::<TestConfig, 1, 256, 1536, 16>::new(TestConfig::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(
+            ADDR,
+            b"GET /first HTTP/1.1\r\n\r\nGET /second HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+    server.poll_write().unwrap();
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\n/first"
+    );
+    assert!(server.inner.closed);
+    assert!(server.clients.is_empty());
+}
+
+#[test]
+fn test_error_response() {
+    use test_utilities::{TestConfig, ADDR};
+
+    let mut server = Server::<TestConfig, 1>::new(TestConfig::default());
+    server.new_connection(ADDR).unwrap();
+    assert_eq!(
+        server.data_received(ADDR, b"GET /\r\n"),
+        Err(Error::InvalidRequestLine)
+    );
+    assert!(server.inner.closed);
+    assert!(server.clients.is_empty());
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nContent-Length: 11\r\nConnection: close\r\n\r\nBad Request"
+    );
+
+    // an invalid pipelined request gets the same response, with the version of the request
+    let mut server = Server::<TestConfig, 1>::new(TestConfig::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(
+            ADDR,
+            b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET / HTTP/1.0\r\nBad\r\n",
+        )
+        .unwrap();
+    assert_eq!(server.poll_write(), Err(Error::InvalidHttpHeader));
+    assert!(server.inner.closed);
+    assert!(server.clients.is_empty());
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.0 200 OK\r\nContent-Length: 1\r\nConnection: keep-alive\r\n\r\n/\
+         HTTP/1.0 400 Bad Request\r\nContent-Type: text/plain\r\nContent-Length: 11\r\nConnection: close\r\n\r\nBad Request"
+    );
+}
+
+#[test]
+fn test_head() {
+    use test_utilities::{TestConfig, ADDR};
+
+    let mut server = Server::<TestConfig, 1>::new(TestConfig::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(ADDR, b"HEAD /head HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n")
+        .unwrap();
+    server.poll_write().unwrap();
+    assert!(!server.inner.closed);
+    // the connection sees a GET request, and the body of the HEAD response is dropped
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n\
+         HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\n/"
+    );
+}
+
+#[test]
+fn test_head_asset() {
+    use test_utilities::ADDR;
+
+    static ASSETS: StaticAssets =
+        StaticAssets::new(&[Asset::new("/", "text/plain", b"0123456789")]);
+
+    /// Serves `ASSETS` in slices of 2 bytes.
+    #[derive(Default)]
+    struct AssetConfig {
+        output: Vec<u8, 256>,
+    }
+
+    impl ServerConfig for AssetConfig {
+        type ConnectionType = AssetConnection<2>;
+
+        fn new_connection(
+            &self,
+            _addr: SocketAddr,
+            method: HttpMethod,
+            path: &str,
+            _version: HttpVersion,
+        ) -> Result<Self::ConnectionType> {
+            Ok(ASSETS.connection(method, path))
+        }
+
+        fn on_client_done_writing(
+            &mut self,
+            _addr: SocketAddr,
+            _client: &mut Self::ConnectionType,
+        ) {
+        }
+
+        fn write(&mut self, _addr: SocketAddr, bytes: &[u8]) -> WriteResult {
+            self.output.extend_from_slice(bytes).unwrap();
+            WriteResult::Ok {
+                bytes_written: bytes.len(),
+            }
+        }
+
+        fn close_connection(&mut self, _addr: SocketAddr) {}
+    }
+
+    let mut server = Server::<AssetConfig, 1>::new(AssetConfig::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(ADDR, b"HEAD / HTTP/1.1\r\n\r\n")
+        .unwrap();
+    server.poll_write().unwrap();
+    // the response is done as soon as the headers are written, the slices of the body are never polled
+    assert!(!server.is_writing());
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nContent-Type: text/plain\r\nContent-Length: 10\r\n\r\n"
+    );
+}
+
+#[test]
+fn test_cors() {
+    use test_utilities::{TestConfigWith, TestSettings, ADDR};
+
+    struct Cors;
+    impl TestSettings for Cors {
+        const CORS: Option<CorsPolicy> = Some(
+            CorsPolicy::new(&["https://example.com"])
+                .with_methods(&[HttpMethod::Get, HttpMethod::Put])
+                .with_headers(&["Content-Type"])
+                .with_max_age(600),
+        );
+    }
+
+    let mut server = Server::<TestConfigWith<Cors>, 1>::new(TestConfigWith::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(
+            ADDR,
+            b"OPTIONS /api HTTP/1.1\r\nOrigin: https://example.com\r\n\
+              Access-Control-Request-Method: PUT\r\n\r\n\
+              GET /api HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n\
+              GET /other HTTP/1.1\r\nOrigin: https://evil.example\r\n\r\n",
+        )
+        .unwrap();
+    server.poll_write().unwrap();
+    assert!(!server.inner.closed);
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.1 204 No Content\r\nVary: Origin\r\n\
+         Access-Control-Allow-Origin: https://example.com\r\n\
+         Access-Control-Allow-Methods: GET, PUT\r\nAccess-Control-Allow-Headers: Content-Type\r\n\
+         Access-Control-Max-Age: 600\r\n\r\n\
+         HTTP/1.1 200 OK\r\nVary: Origin\r\nAccess-Control-Allow-Origin: https://example.com\r\n\
+         Content-Length: 4\r\n\r\n/api\
+         HTTP/1.1 200 OK\r\nVary: Origin\r\nContent-Length: 6\r\n\r\n/other"
+    );
+
+    // other OPTIONS requests go to a connection
+    let mut server = Server::<TestConfigWith<Cors>, 1>::new(TestConfigWith::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(
+            ADDR,
+            b"OPTIONS * HTTP/1.1\r\n\r\n\
+              OPTIONS /api HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n",
+        )
+        .unwrap();
+    server.poll_write().unwrap();
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nVary: Origin\r\nContent-Length: 1\r\n\r\n*\
+         HTTP/1.1 200 OK\r\nVary: Origin\r\nAccess-Control-Allow-Origin: https://example.com\r\n\
+         Content-Length: 4\r\n\r\n/api"
+    );
+}
+
+#[test]
+fn test_limits() {
+    use test_utilities::{TestConfigWith, TestSettings, ADDR};
+
+    struct Limits;
+    impl TestSettings for Limits {
+        const LIMITS: ServerLimits = ServerLimits {
+            max_request_line_length: 24,
+            max_header_length: 32,
+            max_headers: 2,
+            max_body_size: Some(4),
+        };
+    }
+
+    for (request, error) in [
+        (
+            &b"GET /a/very/long/path HTTP/1.1\r\n"[..],
+            Error::RequestLineTooLong,
+        ),
+        (
+            b"GET / HTTP/1.1\r\nX-Long-Header: a much longer value\r\n",
+            Error::HttpHeaderTooLong,
+        ),
+        (
+            b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n",
+            Error::TooManyHeaders,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n",
+            Error::BodyTooLong,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n",
+            Error::BodyTooLong,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1;a-long-chunk-extension=some-value\r\n",
+            Error::InvalidChunk,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\nX-Trailer: a much, much longer value\r\n",
+            Error::HttpHeaderTooLong,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\nA: 1\r\nB: 2\r\n",
+            Error::TooManyHeaders,
+        ),
+    ] {
+        let mut server = Server::<TestConfigWith<Limits>, 1>::new(TestConfigWith::default());
+        server.new_connection(ADDR).unwrap();
+        let result = request
+            .chunks(5)
+            .try_for_each(|chunk| server.data_received(ADDR, chunk));
+        assert_eq!(result, Err(error));
+        assert!(server.inner.closed);
+        let (code, _) = result.unwrap_err().status().unwrap();
+        let mut status_line = heapless::String::<32>::new();
+        core::fmt::write(&mut status_line, format_args!("HTTP/1.1 {code} ")).unwrap();
+        assert!(server.inner.output.starts_with(status_line.as_bytes()));
+    }
+
+    let mut server = Server::<TestConfigWith<Limits>, 1>::new(TestConfigWith::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(ADDR, b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nbody")
+        .unwrap();
+    server.poll_write().unwrap();
+    assert!(!server.inner.closed);
+}
+
+#[test]
+fn test_timeouts() {
+    use test_utilities::{TestConfigWith, TestSettings, ADDR};
+
+    struct Timeouts;
+    impl TestSettings for Timeouts {
+        const TIMEOUTS: ServerTimeouts = ServerTimeouts {
+            header_read: Some(10),
+            body_read: Some(10),
+            idle: Some(20),
+            write: None,
+        };
+    }
+    let new_server = || {
+        let mut server = Server::<TestConfigWith<Timeouts>, 1>::new(TestConfigWith::default());
+        server.new_connection(ADDR).unwrap();
+        server
+    };
+
+    // a connection that never sends anything is closed without a response
+    let mut server = new_server();
+    server.tick(100);
+    assert!(server.tick(119).is_empty());
+    assert!(!server.inner.closed);
+    assert_eq!(server.tick(120), [ADDR]);
+    assert!(server.inner.closed);
+    assert!(server.inner.output.is_empty());
+    assert!(server.clients.is_empty());
+
+    // the headers have to be complete within the timeout, even if the client keeps sending
+    let mut server = new_server();
+    server.data_received(ADDR, b"GET / HTTP/1.1\r\n").unwrap();
+    server.tick(0);
+    server.data_received(ADDR, b"Host: a\r\n").unwrap();
+    server.tick(9);
+    assert!(!server.inner.closed);
+    server.tick(10);
+    assert!(server.inner.closed);
+    assert!(server
+        .inner
+        .output
+        .starts_with(b"HTTP/1.1 408 Request Timeout\r\n"));
+
+    // the body timeout restarts whenever data comes in
+    let mut server = new_server();
+    server
+        .data_received(ADDR, b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\n")
+        .unwrap();
+    server.tick(0);
+    server.data_received(ADDR, b"ab").unwrap();
+    server.tick(5);
+    server.tick(14);
+    assert!(!server.inner.closed);
+    server.tick(15);
+    assert!(server.inner.closed);
+    assert!(server
+        .inner
+        .output
+        .starts_with(b"HTTP/1.1 408 Request Timeout\r\n"));
+
+    // the write timeout restarts whenever the transport accepts part of the response
+    struct WriteTimeout;
+    impl TestSettings for WriteTimeout {
+        const TIMEOUTS: ServerTimeouts = ServerTimeouts {
+            write: Some(10),
+            ..ServerTimeouts::NONE
+        };
+    }
+    let mut server = Server::<TestConfigWith<WriteTimeout>, 1>::new(TestConfigWith::default());
+    server.new_connection(ADDR).unwrap();
+    server.inner.write_budget = Some(0);
+    server
+        .data_received(ADDR, b"GET / HTTP/1.1\r\n\r\n")
+        .unwrap();
+    server.poll_write().unwrap();
+    server.tick(0);
+    server.inner.write_budget = Some(5);
+    server.poll_write().unwrap();
+    server.tick(9);
+    assert!(server.tick(18).is_empty());
+    assert_eq!(server.tick(19), [ADDR]);
+    assert!(server.inner.closed);
+    assert_eq!(server.inner.output.len(), 5);
+}
+
+#[test]
+fn test_eviction() {
+    use core::net::{IpAddr, Ipv4Addr};
+    use test_utilities::{TestConfigWith, TestSettings, ADDR};
+
+    struct Evict;
+    impl TestSettings for Evict {
+        const EVICTION: EvictionPolicy = EvictionPolicy::OldestIdle;
+    }
+    let other = |port| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+
+    let mut server = Server::<TestConfigWith<Evict>, 3>::new(TestConfigWith::default());
+    server.new_connection(ADDR).unwrap();
+    server.new_connection(other(1)).unwrap();
+    server.new_connection(other(4)).unwrap();
+    // both connections are idle after a request, but `ADDR` has been idle the longest
+    server
+        .data_received(ADDR, b"GET / HTTP/1.1\r\n\r\n")
+        .unwrap();
+    server.poll_write().unwrap();
+    server
+        .data_received(other(1), b"GET / HTTP/1.1\r\n\r\n")
+        .unwrap();
+    server.poll_write().unwrap();
+    assert!(!server.inner.closed);
+
+    server.new_connection(other(2)).unwrap();
+    assert!(server.inner.closed);
+    // the other clients keep their order
+    let addresses: Vec<SocketAddr, 3> = server.clients.iter().map(|c| c.address).collect();
+    assert_eq!(addresses, [other(1), other(4), other(2)]);
+
+    // connections that are in the middle of a request, or never sent one, are not idle
+    server.data_received(other(1), b"GET / HT").unwrap();
+    assert_eq!(
+        server.new_connection(other(3)),
+        Err(Error::TooManyConnections)
+    );
+}
+
+#[test]
+fn test_http_1_0() {
+    use test_utilities::{TestConfig, ADDR};
+
+    let mut server = Server::<TestConfig, 1>::new(TestConfig::default());
+    server.new_connection(ADDR).unwrap();
+    server
+        .data_received(ADDR, b"GET / HTTP/1.0\r\n\r\n")
+        .unwrap();
+    server.poll_write().unwrap();
+    assert!(server.inner.closed);
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.0 200 OK\r\nContent-Length: 1\r\nConnection: close\r\n\r\n/"
+    );
+}
+
+#[test]
+fn test_backpressure() {
+    use test_utilities::{TestConfig, ADDR};
+
+    let mut server = Server::<TestConfig, 1, 256, 64>::new(TestConfig::default());
+    server.new_connection(ADDR).unwrap();
+    server.inner.write_budget = Some(10);
+    server
+        .data_received(ADDR, b"GET /backpressure HTTP/1.1\r\n\r\n")
+        .unwrap();
+    server.poll_write().unwrap();
+    assert_eq!(&server.inner.output[..], b"HTTP/1.1 2");
+    assert!(server.is_writing());
+
+    // nothing happens while the transport is blocked
+    server.inner.write_budget = Some(0);
+    server.poll_write().unwrap();
+    assert_eq!(server.inner.output.len(), 10);
+
+    // the response continues exactly where the transport stopped
+    server.inner.write_budget = Some(20);
+    server.poll_write().unwrap();
+    assert_eq!(server.inner.output.len(), 30);
+    server.inner.write_budget = None;
+    server.poll_write().unwrap();
+    assert!(!server.is_writing());
+    assert!(!server.inner.closed);
+    assert_eq!(
+        core::str::from_utf8(&server.inner.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n/backpressure"
+    );
+
+    // a blocked response can't write more than fits in the TX buffer
+    server.inner.write_budget = Some(0);
+    server
+        .data_received(
+            ADDR,
+            b"GET /a/path/that/is/much/too/long/for/the/buffer HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+    assert_eq!(server.poll_write(), Err(Error::TxBufferFull));
+    assert!(server.inner.closed);
+    assert!(server.clients.is_empty());
+}
@@ -0,0 +1,256 @@
+use crate::http_utilities::header_parameters;
+use crate::{Error, HeaderName, MediaType, Result};
+use heapless::Vec;
+
+/// The longest boundary that is allowed by RFC 2046
+const MAX_BOUNDARY_LENGTH: usize = 70;
+
+/// Incremental parser for `multipart/form-data` request bodies, used for file uploads.
+///
+/// Create this from the `Content-Type` header in `Connection::header_received`, and feed it with the bytes from
+/// `Connection::body_received`. The data of the parts is passed on as it comes in, only the headers of a part are
+/// buffered. The headers of a single part have to fit in `HEADER_SIZE` bytes.
+///
+/// When the body is done, `is_done` should return `true`. Otherwise the body was cut off before the last part.
+pub struct MultipartParser<const HEADER_SIZE: usize> {
+    /// `\r\n--` followed by the boundary
+    delimiter: Vec<u8, { MAX_BOUNDARY_LENGTH + 4 }>,
+    /// The amount of bytes of `delimiter` that are matched at the end of the data so far
+    matched: usize,
+    state: MultipartState,
+    headers: Vec<u8, HEADER_SIZE>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum MultipartState {
+    /// Skipping everything before the first boundary
+    Preamble,
+    /// Read a boundary, waiting for either the end of the line or `--`
+    AfterBoundary,
+    /// Read the first `-` of the `--` after the last boundary
+    ClosingDash,
+    /// Reading the headers of a part. `empty_line` is true if the current line has no content yet
+    Headers {
+        empty_line: bool,
+    },
+    Data,
+    /// Read the last boundary, everything after this is ignored
+    Done,
+}
+
+/// Something that happened in a multipart body, see `MultipartParser::feed`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MultipartEvent<'a> {
+    /// A new part starts, the data of this part follows
+    PartStart(PartHeaders<'a>),
+    /// The next bit of data of the current part
+    Data(&'a [u8]),
+    /// The current part is done
+    PartEnd,
+}
+
+/// The headers of a single part of a multipart body.
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct PartHeaders<'a> {
+    /// The name of the form field
+    pub name: Option<&'a str>,
+    /// The name of the uploaded file, if this is a file
+    pub filename: Option<&'a str>,
+    pub content_type: Option<&'a str>,
+}
+
+impl<const HEADER_SIZE: usize> MultipartParser<HEADER_SIZE> {
+    /// Create a parser from the value of the `Content-Type` header, e.g.
+    /// `multipart/form-data; boundary=----WebKitFormBoundary7MA4YWxkTrZu0gW`.
+    pub fn new(content_type: &str) -> Result<Self> {
+        let media_type = MediaType::parse(content_type).map_err(|_| Error::InvalidMultipart)?;
+        let boundary = media_type
+            .parameter("boundary")
+            .filter(|b| {
+                media_type.is("multipart", "*") && !b.is_empty() && !b.contains(['\r', '\n'])
+            })
+            .ok_or(Error::InvalidMultipart)?;
+
+        let mut delimiter = Vec::new();
+        delimiter
+            .extend_from_slice(b"\r\n--")
+            .and_then(|()| delimiter.extend_from_slice(boundary.as_bytes()))
+            .map_err(|()| Error::InvalidMultipart)?;
+        Ok(Self {
+            delimiter,
+            // The first boundary is usually at the very start of the body, without a newline in front of it
+            matched: 2,
+            state: MultipartState::Preamble,
+            headers: Vec::new(),
+        })
+    }
+
+    /// Returns `true` once the last boundary has been read.
+    pub fn is_done(&self) -> bool {
+        self.state == MultipartState::Done
+    }
+
+    /// Parse the next part of the body, calling `on_event` for every part that starts or ends and every bit of data
+    /// of a part.
+    pub fn feed(
+        &mut self,
+        data: &[u8],
+        mut on_event: impl FnMut(MultipartEvent<'_>) -> Result,
+    ) -> Result {
+        let mut i = 0;
+        // the start of the part data in `data` that has not been passed to `on_event` yet
+        let mut data_start = 0;
+        while let Some(&byte) = data.get(i) {
+            match self.state {
+                MultipartState::Preamble | MultipartState::Data => {
+                    if byte == self.delimiter[self.matched] {
+                        if self.matched == 0 && self.state == MultipartState::Data && data_start < i
+                        {
+                            on_event(MultipartEvent::Data(&data[data_start..i]))?;
+                        }
+                        self.matched += 1;
+                        i += 1;
+                        data_start = i;
+                        if self.matched == self.delimiter.len() {
+                            self.matched = 0;
+                            if self.state == MultipartState::Data {
+                                on_event(MultipartEvent::PartEnd)?;
+                            }
+                            self.state = MultipartState::AfterBoundary;
+                        }
+                        continue;
+                    }
+                    if self.matched > 0 {
+                        // This was not a delimiter after all, so the part that did match is data. The boundary can't
+                        // contain a `\r`, so the next delimiter can only start at the current byte.
+                        if self.state == MultipartState::Data {
+                            on_event(MultipartEvent::Data(&self.delimiter[..self.matched]))?;
+                        }
+                        self.matched = 0;
+                        data_start = i;
+                        continue;
+                    }
+                }
+                MultipartState::AfterBoundary => match byte {
+                    b'-' => self.state = MultipartState::ClosingDash,
+                    b'\n' => {
+                        self.headers.clear();
+                        self.state = MultipartState::Headers { empty_line: true };
+                    }
+                    // whitespace is allowed after the boundary
+                    b'\r' | b' ' | b'\t' => {}
+                    _ => return Err(Error::InvalidMultipart),
+                },
+                MultipartState::ClosingDash if byte == b'-' => self.state = MultipartState::Done,
+                MultipartState::ClosingDash => return Err(Error::InvalidMultipart),
+                MultipartState::Headers { empty_line } => {
+                    self.headers
+                        .push(byte)
+                        .map_err(|_| Error::MultipartHeaderTooLong)?;
+                    self.state = match byte {
+                        b'\n' if empty_line => {
+                            on_event(MultipartEvent::PartStart(part_headers(&self.headers)?))?;
+                            data_start = i + 1;
+                            MultipartState::Data
+                        }
+                        b'\n' => MultipartState::Headers { empty_line: true },
+                        b'\r' => MultipartState::Headers { empty_line },
+                        _ => MultipartState::Headers { empty_line: false },
+                    };
+                }
+                MultipartState::Done => break,
+            }
+            i += 1;
+        }
+        if self.state == MultipartState::Data && self.matched == 0 && data_start < data.len() {
+            on_event(MultipartEvent::Data(&data[data_start..]))?;
+        }
+        Ok(())
+    }
+}
+
+fn part_headers(headers: &[u8]) -> Result<PartHeaders<'_>> {
+    let headers = core::str::from_utf8(headers).map_err(|_| Error::InvalidUtf8)?;
+    let mut part = PartHeaders::default();
+    for line in headers.lines().filter(|line| !line.trim().is_empty()) {
+        let (key, value) = line.split_once(':').ok_or(Error::InvalidMultipart)?;
+        match HeaderName::from(key.trim()) {
+            HeaderName::ContentDisposition => {
+                let (_, parameters) = value.split_once(';').unwrap_or((value, ""));
+                for (key, value) in header_parameters(parameters) {
+                    if key.eq_ignore_ascii_case("name") {
+                        part.name = Some(value);
+                    } else if key.eq_ignore_ascii_case("filename") {
+                        part.filename = Some(value);
+                    }
+                }
+            }
+            HeaderName::ContentType => part.content_type = Some(value.trim()),
+            _ => {}
+        }
+    }
+    Ok(part)
+}
+
+#[test]
+fn test_multipart_parser() {
+    use heapless::String;
+
+    #[derive(Debug)]
+    struct PartInfo {
+        name: String<16>,
+        filename: Option<String<16>>,
+        content_type: Option<String<32>>,
+    }
+
+    let body = b"preamble\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"version\"\r\n\
+        \r\n\
+        1.2.3\r\n--XyZ \r\n\
+        Content-Disposition: form-data; name=\"firmware\"; filename=\"fw.bin\"\r\n\
+        Content-Type: application/octet-stream\r\n\
+        \r\n\
+        \r\n\r\n--Xy\r\n--XyY\r\n--XyZ--\r\nepilogue";
+
+    for chunk_size in [1, 7, body.len()] {
+        let mut parser =
+            MultipartParser::<128>::new("multipart/form-data; boundary=\"XyZ\"").unwrap();
+        let mut parts: Vec<(PartInfo, Vec<u8, 32>), 2> = Vec::new();
+        for chunk in body.chunks(chunk_size) {
+            parser
+                .feed(chunk, |event| {
+                    match event {
+                        MultipartEvent::PartStart(headers) => {
+                            let info = PartInfo {
+                                name: headers.name.unwrap().try_into().unwrap(),
+                                filename: headers.filename.map(|f| f.try_into().unwrap()),
+                                content_type: headers.content_type.map(|c| c.try_into().unwrap()),
+                            };
+                            parts.push((info, Vec::new())).unwrap();
+                        }
+                        MultipartEvent::Data(data) => {
+                            parts.last_mut().unwrap().1.extend_from_slice(data).unwrap();
+                        }
+                        MultipartEvent::PartEnd => {}
+                    }
+                    Ok(())
+                })
+                .unwrap();
+        }
+        assert!(parser.is_done());
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].0.name, "version");
+        assert_eq!(parts[0].0.filename, None);
+        assert_eq!(parts[0].1, b"1.2.3");
+        assert_eq!(parts[1].0.name, "firmware");
+        assert_eq!(parts[1].0.filename.as_deref(), Some("fw.bin"));
+        assert_eq!(
+            parts[1].0.content_type.as_deref(),
+            Some("application/octet-stream")
+        );
+        assert_eq!(parts[1].1, b"\r\n\r\n--Xy\r\n--XyY");
+    }
+
+    assert!(MultipartParser::<128>::new("text/plain; boundary=abc").is_err());
+    assert!(MultipartParser::<128>::new("multipart/form-data").is_err());
+}
@@ -0,0 +1,399 @@
+use crate::traits::formatted_length;
+use crate::{HeaderName, ResponseWriter, WriteResult};
+use core::{fmt, ops::Range};
+use heapless::Vec;
+
+/// The boundary between the parts of a `multipart/byteranges` body
+const BOUNDARY: &str = "3d6b6a416f9b5ea4c2b1";
+
+/// Captures the `Range` header of a request, see `RangeResponse` for sending the requested ranges.
+///
+/// Up to `RANGES` ranges are accepted. A header with more ranges, or with a syntax error, is ignored like RFC 9110
+/// section 14.2 allows, so the full response is sent instead.
+///
+/// `Range` is only defined for `GET` requests, so don't use this for other methods.
+pub struct RangeRequest<const RANGES: usize = 4> {
+    ranges: Option<Vec<RangeSpec, RANGES>>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum RangeSpec {
+    /// `first-last` or `first-`
+    From { first: u64, last: Option<u64> },
+    /// `-length`, the last `length` bytes
+    Suffix(u64),
+}
+
+impl<const RANGES: usize> RangeRequest<RANGES> {
+    pub const fn new() -> Self {
+        Self { ranges: None }
+    }
+
+    /// Returns `true` if the header was the `Range` header.
+    pub fn header_received(&mut self, key: &str, value: &str) -> bool {
+        if HeaderName::from(key) != HeaderName::Range {
+            return false;
+        }
+        self.ranges = parse_ranges(value);
+        true
+    }
+
+    /// Resolve the requested ranges against a response of `total_length` bytes.
+    pub fn response(&self, total_length: u64, content_type: &'static str) -> RangeResponse<RANGES> {
+        let Some(specs) = &self.ranges else {
+            return RangeResponse::full(total_length, content_type);
+        };
+        let mut ranges = Vec::new();
+        for spec in specs {
+            let range = match *spec {
+                RangeSpec::From { first, last } => {
+                    first..last.map_or(total_length, |last| {
+                        total_length.min(last.saturating_add(1))
+                    })
+                }
+                RangeSpec::Suffix(length) => total_length.saturating_sub(length)..total_length,
+            };
+            // ranges that start after the end are unsatisfiable, they are skipped if other ranges are satisfiable
+            if range.start < range.end {
+                // this can't fail, there are never more ranges than specs
+                let _ = ranges.push(range);
+            }
+        }
+        RangeResponse {
+            total_length,
+            content_type,
+            status: if ranges.is_empty() {
+                RangeStatus::Unsatisfiable
+            } else {
+                RangeStatus::Partial
+            },
+            ranges,
+            index: 0,
+            part_started: false,
+        }
+    }
+}
+
+impl<const RANGES: usize> Default for RangeRequest<RANGES> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parse the value of a `Range` header like `bytes=0-499, -500`. Returns `None` if it should be ignored.
+fn parse_ranges<const RANGES: usize>(value: &str) -> Option<Vec<RangeSpec, RANGES>> {
+    let (unit, ranges) = value.split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+    let number = |s: &str| {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse::<u64>().ok()
+    };
+
+    let mut specs = Vec::new();
+    // empty list elements are allowed, like in `bytes=0-1,,2-3`
+    for range in ranges.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+        let (first, last) = range.split_once('-')?;
+        let spec = if first.is_empty() {
+            RangeSpec::Suffix(number(last)?)
+        } else {
+            let first = number(first)?;
+            let last = if last.is_empty() {
+                None
+            } else {
+                Some(number(last)?)
+            };
+            if last.is_some_and(|last| last < first) {
+                return None;
+            }
+            RangeSpec::From { first, last }
+        };
+        specs.push(spec).ok()?;
+    }
+    if specs.is_empty() {
+        None
+    } else {
+        Some(specs)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum RangeStatus {
+    /// `200 OK` with the entire body
+    Full,
+    /// `206 Partial Content`, with a `multipart/byteranges` body if there are multiple ranges
+    Partial,
+    /// `416 Range Not Satisfiable`
+    Unsatisfiable,
+}
+
+/// Writes the parts of a response that were requested with a `RangeRequest`, the response can come from any source
+/// where the total length is known.
+///
+/// Call `write_headers` at the start of the response, then `poll_body` from `Connection::poll_write` until it returns
+/// `WriteResult::EndWriting`.
+pub struct RangeResponse<const RANGES: usize = 4> {
+    total_length: u64,
+    content_type: &'static str,
+    status: RangeStatus,
+    /// The satisfiable ranges, the start of the current range moves forward while it is written
+    ranges: Vec<Range<u64>, RANGES>,
+    /// The range that is currently written
+    index: usize,
+    /// `true` if the headers of the current part of a `multipart/byteranges` body are written
+    part_started: bool,
+}
+
+impl<const RANGES: usize> RangeResponse<RANGES> {
+    /// A response with the full body, for requests without a `Range` header.
+    pub fn full(total_length: u64, content_type: &'static str) -> Self {
+        let mut ranges = Vec::new();
+        if total_length > 0 {
+            // `RANGES` is 0 if this fails, in which case ranges are never satisfiable anyway
+            let _ = ranges.push(0..total_length);
+        }
+        Self {
+            total_length,
+            content_type,
+            status: RangeStatus::Full,
+            ranges,
+            index: 0,
+            part_started: false,
+        }
+    }
+
+    /// The status code of the response: 200, 206 or 416.
+    pub fn status(&self) -> u16 {
+        match self.status {
+            RangeStatus::Full => 200,
+            RangeStatus::Partial => 206,
+            RangeStatus::Unsatisfiable => 416,
+        }
+    }
+
+    /// Returns `true` if there is no more body to write.
+    pub fn is_done(&self) -> bool {
+        self.index >= self.ranges.len()
+    }
+
+    fn is_multipart(&self) -> bool {
+        self.ranges.len() > 1 && self.status == RangeStatus::Partial
+    }
+
+    fn part_header(&self, index: usize) -> PartHeader {
+        PartHeader {
+            first: index == 0,
+            content_type: self.content_type,
+            range: self.ranges[index].clone(),
+            total_length: self.total_length,
+        }
+    }
+
+    /// Write the status line, `Content-Type`, `Content-Length` and the range headers.
+    ///
+    /// Other headers can be written after this, followed by `ResponseWriter::end_headers_start_body`.
+    pub fn write_headers(&self, writer: &mut dyn ResponseWriter) -> WriteResult {
+        match self.status {
+            RangeStatus::Full => {
+                writer.write_status(200, "OK")?;
+                writer.write_response_header("Accept-Ranges", "bytes")?;
+                writer.write_response_header("Content-Type", self.content_type)?;
+                writer.write_content_length(self.total_length)
+            }
+            RangeStatus::Unsatisfiable => {
+                writer.write_status(416, "Range Not Satisfiable")?;
+                write!(writer, "Content-Range: bytes */{}\r\n", self.total_length)?;
+                writer.write_content_length(0)
+            }
+            RangeStatus::Partial if !self.is_multipart() => {
+                let range = &self.ranges[0];
+                writer.write_status(206, "Partial Content")?;
+                writer.write_response_header("Content-Type", self.content_type)?;
+                write!(
+                    writer,
+                    "Content-Range: bytes {}-{}/{}\r\n",
+                    range.start,
+                    range.end - 1,
+                    self.total_length
+                )?;
+                writer.write_content_length(range.end - range.start)
+            }
+            RangeStatus::Partial => {
+                let mut length = CLOSE_DELIMITER_LENGTH;
+                for index in 0..self.ranges.len() {
+                    let range = &self.ranges[index];
+                    length += formatted_length(format_args!("{}", self.part_header(index))) as u64;
+                    length += range.end - range.start;
+                }
+                writer.write_status(206, "Partial Content")?;
+                write!(
+                    writer,
+                    "Content-Type: multipart/byteranges; boundary={BOUNDARY}\r\n"
+                )?;
+                writer.write_content_length(length)
+            }
+        }
+    }
+
+    /// Write the next part of the body.
+    ///
+    /// `write_range` is called with the range of the body that should be written next, and returns how many bytes
+    /// of it were written. It doesn't have to write the entire range at once, the rest is requested on the next call.
+    pub fn poll_body(
+        &mut self,
+        writer: &mut dyn ResponseWriter,
+        mut write_range: impl FnMut(&mut dyn ResponseWriter, Range<u64>) -> WriteResult,
+    ) -> WriteResult {
+        if self.is_done() {
+            return WriteResult::EndWriting;
+        }
+        if self.is_multipart() && !self.part_started {
+            write!(writer, "{}", self.part_header(self.index))?;
+            self.part_started = true;
+        }
+
+        let range = self.ranges[self.index].clone();
+        let bytes_written = write_range(writer, range.clone())?;
+        self.ranges[self.index].start = range.end.min(range.start + bytes_written as u64);
+        if self.ranges[self.index].is_empty() {
+            self.index += 1;
+            self.part_started = false;
+            if self.is_done() {
+                if self.is_multipart() {
+                    write!(writer, "\r\n--{BOUNDARY}--\r\n")?;
+                }
+                return WriteResult::EndWriting;
+            }
+        }
+        WriteResult::Ok { bytes_written }
+    }
+}
+
+/// The length of `\r\n--{BOUNDARY}--\r\n`
+pub(crate) const CLOSE_DELIMITER_LENGTH: u64 = BOUNDARY.len() as u64 + 8;
+
+/// The delimiter and headers in front of a part of a `multipart/byteranges` body.
+struct PartHeader {
+    first: bool,
+    content_type: &'static str,
+    range: Range<u64>,
+    total_length: u64,
+}
+
+impl fmt::Display for PartHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.first {
+            f.write_str("\r\n")?;
+        }
+        write!(
+            f,
+            "--{BOUNDARY}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+            self.content_type,
+            self.range.start,
+            self.range.end - 1,
+            self.total_length
+        )
+    }
+}
+
+#[test]
+fn test_parse_ranges() {
+    use RangeSpec::*;
+
+    assert_eq!(
+        parse_ranges::<4>("bytes=0-499, 500-,-200,,").unwrap(),
+        [
+            From {
+                first: 0,
+                last: Some(499)
+            },
+            From {
+                first: 500,
+                last: None
+            },
+            Suffix(200),
+        ]
+    );
+    for ignored in [
+        "items=0-1",
+        "bytes=",
+        "bytes=1",
+        "bytes=5-4",
+        "bytes=a-b",
+        "bytes=-",
+        "bytes=0-1,2-3,4-5",
+    ] {
+        assert_eq!(parse_ranges::<2>(ignored), None, "{ignored}");
+    }
+}
+
+#[test]
+fn test_range_response() {
+    use crate::test_utilities::{writer, TestConfig};
+    use crate::traits::ResponseState;
+
+    let body = b"0123456789";
+    let respond = |range: &str| {
+        let mut request = RangeRequest::<4>::new();
+        request.header_received("Range", range);
+        let mut response = request.response(body.len() as u64, "text/plain");
+
+        let mut config = TestConfig::default();
+        let mut state = ResponseState::default();
+        let mut writer = writer(&mut config, &mut state);
+        response.write_headers(&mut writer);
+        writer.end_headers_start_body();
+        // write at most 2 bytes at a time
+        while !matches!(
+            response.poll_body(&mut writer, |writer, range| {
+                let end = range.end.min(range.start + 2);
+                writer.write_bytes(&body[range.start as usize..end as usize])
+            }),
+            WriteResult::EndWriting
+        ) {}
+        let output = core::str::from_utf8(&config.output).unwrap();
+        let (headers, body) = output.split_once("\r\n\r\n").unwrap();
+        let content_length = headers
+            .split("\r\n")
+            .find_map(|h| h.strip_prefix("Content-Length: "))
+            .unwrap();
+        assert_eq!(content_length.parse(), Ok(body.len()));
+        heapless::String::<512>::try_from(output).unwrap()
+    };
+
+    assert_eq!(
+        respond("bytes=8-20"),
+        "HTTP/1.1 206 Partial Content\r\nContent-Type: text/plain\r\nContent-Range: bytes 8-9/10\r\n\
+         Content-Length: 2\r\n\r\n89"
+    );
+    assert_eq!(
+        respond("bytes=10-, 2-4"),
+        "HTTP/1.1 206 Partial Content\r\nContent-Type: text/plain\r\nContent-Range: bytes 2-4/10\r\n\
+         Content-Length: 3\r\n\r\n234"
+    );
+    assert_eq!(
+        respond("bytes=0-2,-3"),
+        "HTTP/1.1 206 Partial Content\r\n\
+         Content-Type: multipart/byteranges; boundary=3d6b6a416f9b5ea4c2b1\r\nContent-Length: 198\r\n\r\n\
+         --3d6b6a416f9b5ea4c2b1\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-2/10\r\n\r\n012\r\n\
+         --3d6b6a416f9b5ea4c2b1\r\nContent-Type: text/plain\r\nContent-Range: bytes 7-9/10\r\n\r\n789\r\n\
+         --3d6b6a416f9b5ea4c2b1--\r\n"
+    );
+    assert_eq!(
+        respond("bytes=10-"),
+        "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */10\r\nContent-Length: 0\r\n\r\n"
+    );
+    assert_eq!(
+        respond("bytes=5-4"),
+        "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nContent-Type: text/plain\r\nContent-Length: 10\r\n\r\n\
+         0123456789"
+    );
+    assert_eq!(
+        respond("bytes=0-18446744073709551615"),
+        "HTTP/1.1 206 Partial Content\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-9/10\r\n\
+         Content-Length: 10\r\n\r\n0123456789"
+    );
+}
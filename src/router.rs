@@ -0,0 +1,345 @@
+use crate::{
+    Connection, Error, HttpMethod, HttpVersion, ReadResult, ResponseWriter, Result, SocketAddr,
+    Uri, WriteResult,
+};
+use heapless::Vec;
+
+/// Maps requests to `Connection`s based on the method and path, for use in `ServerConfig::new_connection`.
+///
+/// The routes are a const table, and are checked in order:
+///
+/// ```ignore
+/// static ROUTER: Router<MyConnection> = Router::new(&[
+///     Route::new(HttpMethod::Get, "/", MyConnection::index),
+///     Route::new(HttpMethod::Get, "/api/sensor/:id", MyConnection::sensor),
+///     Route::new(HttpMethod::Post, "/api/sensor/:id", MyConnection::update_sensor),
+/// ]);
+/// ```
+///
+/// A segment starting with `:` matches any single segment of the path, a segment starting with `*` matches the rest of
+/// the path. The matched values are available in `RouteRequest::parameters`, at most `PARAMETERS` of them.
+///
+/// Requests that don't match any route get a `404 Not Found`, or a `405 Method Not Allowed` if the path matches but the
+/// method doesn't.
+pub struct Router<C: Connection + 'static, const PARAMETERS: usize = 4> {
+    routes: &'static [Route<C, PARAMETERS>],
+}
+
+pub struct Route<C: Connection, const PARAMETERS: usize = 4> {
+    method: HttpMethod,
+    pattern: &'static str,
+    handler: fn(&RouteRequest<'_, PARAMETERS>) -> Result<C>,
+}
+
+/// The request that is passed to the handler of a `Route`.
+pub struct RouteRequest<'a, const PARAMETERS: usize> {
+    pub addr: SocketAddr,
+    pub method: HttpMethod,
+    pub uri: Uri<'a>,
+    pub version: HttpVersion,
+    /// The segments of the path matched by `:name` and `*name` in the pattern, without the `:` or `*`
+    pub parameters: PathParameters<'a, PARAMETERS>,
+}
+
+/// The values of the parameters in a route pattern, e.g. `id` in `/api/sensor/:id`. The values are not decoded.
+pub struct PathParameters<'a, const PARAMETERS: usize> {
+    values: Vec<(&'static str, &'a str), PARAMETERS>,
+}
+
+impl<'a, const PARAMETERS: usize> PathParameters<'a, PARAMETERS> {
+    /// Get the value of the parameter called `name`
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.values
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    }
+
+    /// Iterate over the names and values of all parameters
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &'a str)> + '_ {
+        self.values.iter().copied()
+    }
+}
+
+impl<C: Connection, const PARAMETERS: usize> Route<C, PARAMETERS> {
+    /// Panics if `pattern` has more than `PARAMETERS` parameters, which fails the build for a `Router` in a `static`.
+    pub const fn new(
+        method: HttpMethod,
+        pattern: &'static str,
+        handler: fn(&RouteRequest<'_, PARAMETERS>) -> Result<C>,
+    ) -> Self {
+        assert!(
+            parameter_count(pattern) <= PARAMETERS,
+            "the route pattern has more parameters than the router's PARAMETERS"
+        );
+        Self {
+            method,
+            pattern,
+            handler,
+        }
+    }
+}
+
+impl<C: Connection + 'static, const PARAMETERS: usize> Router<C, PARAMETERS> {
+    pub const fn new(routes: &'static [Route<C, PARAMETERS>]) -> Self {
+        Self { routes }
+    }
+
+    /// Find the route for a request and call its handler. This can be called directly from
+    /// `ServerConfig::new_connection`.
+    pub fn route(
+        &self,
+        addr: SocketAddr,
+        method: HttpMethod,
+        path: &str,
+        version: HttpVersion,
+    ) -> Result<Routed<C>> {
+        let uri = Uri::parse(path)?;
+        let mut allowed = AllowedMethods::default();
+        for route in self.routes {
+            let Some(parameters) = match_path(route.pattern, uri.path()) else {
+                continue;
+            };
+            if route.method != method {
+                allowed.insert(route.method);
+                // the server answers HEAD requests with the GET route
+                if route.method == HttpMethod::Get {
+                    allowed.insert(HttpMethod::Head);
+                }
+                continue;
+            }
+            let request = RouteRequest {
+                addr,
+                method,
+                uri,
+                version,
+                parameters,
+            };
+            return (route.handler)(&request).map(Routed::Handler);
+        }
+        if allowed.is_empty() {
+            Ok(Routed::NotFound)
+        } else {
+            Ok(Routed::MethodNotAllowed(allowed))
+        }
+    }
+}
+
+/// The amount of `:name` and `*name` segments in `pattern`.
+const fn parameter_count(pattern: &str) -> usize {
+    let bytes = pattern.as_bytes();
+    let mut count = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let segment_start = i == 0 || bytes[i - 1] == b'/';
+        if segment_start && (bytes[i] == b':' || bytes[i] == b'*') {
+            count += 1;
+        }
+        i += 1;
+    }
+    count
+}
+
+fn match_path<'a, const PARAMETERS: usize>(
+    pattern: &'static str,
+    path: &'a str,
+) -> Option<PathParameters<'a, PARAMETERS>> {
+    let mut parameters = PathParameters { values: Vec::new() };
+    let mut remaining = Some(path);
+    for segment in pattern.split('/') {
+        let rest = remaining?;
+        if let Some(name) = segment.strip_prefix('*') {
+            parameters.values.push((name, rest)).ok()?;
+            return Some(parameters);
+        }
+        let (part, next) = rest
+            .split_once('/')
+            .map_or((rest, None), |(part, next)| (part, Some(next)));
+        if let Some(name) = segment.strip_prefix(':') {
+            if part.is_empty() {
+                return None;
+            }
+            parameters.values.push((name, part)).ok()?;
+        } else if segment != part {
+            return None;
+        }
+        remaining = next;
+    }
+    remaining.is_none().then_some(parameters)
+}
+
+/// The `Connection` created by a `Router`. This is either the connection of the matching route, or a `404` or `405`
+/// response.
+pub enum Routed<C: Connection> {
+    Handler(C),
+    NotFound,
+    MethodNotAllowed(AllowedMethods),
+}
+
+impl<C: Connection> Connection for Routed<C> {
+    fn header_received(&mut self, key: &str, value: &str) -> ReadResult {
+        match self {
+            Self::Handler(connection) => connection.header_received(key, value),
+            _ => ReadResult::Ok,
+        }
+    }
+
+    fn switch_from_headers_to_body(&mut self) -> ReadResult {
+        match self {
+            Self::Handler(connection) => connection.switch_from_headers_to_body(),
+            _ => ReadResult::Ok,
+        }
+    }
+
+    fn body_received(&mut self, bytes: &[u8]) -> ReadResult {
+        match self {
+            Self::Handler(connection) => connection.body_received(bytes),
+            // read the body anyway, so the connection can be reused
+            _ => ReadResult::Ok,
+        }
+    }
+
+    fn poll_write(&mut self, writer: &mut dyn ResponseWriter) -> WriteResult {
+        match self {
+            Self::Handler(connection) => connection.poll_write(writer),
+            Self::NotFound => {
+                writer.write_status(404, "Not Found")?;
+                writer.write_response_header("Content-Type", "text/plain")?;
+                writer.write_content_length(9)?;
+                writer.end_headers_start_body()?;
+                write!(writer, "Not Found")?;
+                WriteResult::EndWriting
+            }
+            Self::MethodNotAllowed(allowed) => {
+                writer.write_status(405, "Method Not Allowed")?;
+                // long enough for all methods
+                let mut value = heapless::String::<64>::new();
+                if core::fmt::write(&mut value, format_args!("{allowed}")).is_err() {
+                    return WriteResult::Err(Error::InvalidClientState);
+                }
+                writer.write_response_header("Allow", &value)?;
+                writer.write_content_length(0)?;
+                writer.end_headers_start_body()?;
+                WriteResult::EndWriting
+            }
+        }
+    }
+}
+
+/// A set of `HttpMethod`s, used for the `Allow` header of a `405 Method Not Allowed` response.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct AllowedMethods(u16);
+
+impl AllowedMethods {
+    const ALL: [HttpMethod; 9] = [
+        HttpMethod::Get,
+        HttpMethod::Head,
+        HttpMethod::Post,
+        HttpMethod::Put,
+        HttpMethod::Delete,
+        HttpMethod::Connect,
+        HttpMethod::Options,
+        HttpMethod::Trace,
+        HttpMethod::Patch,
+    ];
+
+    pub fn insert(&mut self, method: HttpMethod) {
+        self.0 |= 1 << method as u16;
+    }
+
+    pub fn contains(&self, method: HttpMethod) -> bool {
+        self.0 & (1 << method as u16) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = HttpMethod> + '_ {
+        Self::ALL.into_iter().filter(|m| self.contains(*m))
+    }
+}
+
+impl core::fmt::Display for AllowedMethods {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (i, method) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(method.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_router() {
+    use crate::test_utilities::{writer, TestConfig, TestConnection, ADDR};
+    use crate::traits::ResponseState;
+
+    fn sensor(request: &RouteRequest<'_, 2>) -> Result<TestConnection> {
+        Ok(TestConnection {
+            method: request.method,
+            path: request.parameters.get("id").unwrap().try_into().unwrap(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        })
+    }
+    fn files(request: &RouteRequest<'_, 2>) -> Result<TestConnection> {
+        Ok(TestConnection {
+            method: request.method,
+            path: request.parameters.get("file").unwrap().try_into().unwrap(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        })
+    }
+    static ROUTER: Router<TestConnection, 2> = Router::new(&[
+        Route::new(HttpMethod::Get, "/api/sensor/:id", sensor),
+        Route::new(HttpMethod::Put, "/api/sensor/:id", sensor),
+        Route::new(HttpMethod::Get, "/static/*file", files),
+    ]);
+    let route = |method, path| ROUTER.route(ADDR, method, path, HttpVersion::Http11);
+
+    let Ok(Routed::Handler(connection)) = route(HttpMethod::Get, "/api/sensor/3?unit=c") else {
+        panic!("expected a handler");
+    };
+    assert_eq!(connection.path, "3");
+    let Ok(Routed::Handler(connection)) = route(HttpMethod::Get, "/static/js/app.js") else {
+        panic!("expected a handler");
+    };
+    assert_eq!(connection.path, "js/app.js");
+    assert!(matches!(
+        route(HttpMethod::Get, "/api/sensor/"),
+        Ok(Routed::NotFound)
+    ));
+    assert!(matches!(
+        route(HttpMethod::Get, "/api/sensor/3/4"),
+        Ok(Routed::NotFound)
+    ));
+    assert!(matches!(route(HttpMethod::Get, "/"), Ok(Routed::NotFound)));
+
+    let Ok(mut connection) = route(HttpMethod::Delete, "/api/sensor/3") else {
+        panic!("expected a connection");
+    };
+    let mut config = TestConfig::default();
+    let mut response = ResponseState::default();
+    connection.poll_write(&mut writer(&mut config, &mut response));
+    assert_eq!(
+        core::str::from_utf8(&config.output).unwrap(),
+        "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, HEAD, PUT\r\nContent-Length: 0\r\n\r\n"
+    );
+
+    assert_eq!(parameter_count("/api/:group/:id/*rest"), 3);
+    assert_eq!(parameter_count("/a:b/*"), 1);
+}
+
+#[test]
+#[should_panic(expected = "more parameters")]
+fn test_too_many_parameters() {
+    use crate::test_utilities::TestConnection;
+
+    fn handler(_: &RouteRequest<'_, 1>) -> Result<TestConnection> {
+        Err(Error::MethodNotAllowed)
+    }
+    Route::new(HttpMethod::Get, "/:a/:b", handler);
+}
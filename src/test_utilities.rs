@@ -0,0 +1,140 @@
+//! Mock implementations of `ServerConfig` and `Connection` that are used in the unit tests.
+
+use crate::traits::{ResponseState, ServerConfigWriter};
+use crate::{
+    Connection, CorsPolicy, EvictionPolicy, HttpMethod, HttpVersion, ReadResult, ResponseWriter,
+    Result, ServerConfig, ServerLimits, ServerTimeouts, SocketAddr, WriteResult,
+};
+use core::marker::PhantomData;
+use core::net::{IpAddr, Ipv4Addr};
+use heapless::{String, Vec};
+
+pub(crate) const ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1234);
+
+/// Overrides the constants of `ServerConfig` for `TestConfigWith`.
+pub(crate) trait TestSettings {
+    const CORS: Option<CorsPolicy> = None;
+    const LIMITS: ServerLimits = ServerLimits::DEFAULT;
+    const TIMEOUTS: ServerTimeouts = ServerTimeouts::DEFAULT;
+    const EVICTION: EvictionPolicy = EvictionPolicy::Reject;
+}
+
+impl TestSettings for () {}
+
+/// Stores everything that is written to the client in `output`.
+pub(crate) struct TestConfigWith<S: TestSettings> {
+    pub(crate) output: Vec<u8, 2048>,
+    pub(crate) closed: bool,
+    /// If set, `write` accepts this many more bytes, and would block after that.
+    pub(crate) write_budget: Option<usize>,
+    settings: PhantomData<S>,
+}
+
+/// A `TestConfigWith` that uses the default settings.
+pub(crate) type TestConfig = TestConfigWith<()>;
+
+/// A writer for the response to a keep-alive HTTP/1.1 request from `ADDR`, that writes straight to `config`.
+pub(crate) fn writer<'a, S: TestSettings>(
+    config: &'a mut TestConfigWith<S>,
+    response: &'a mut ResponseState,
+) -> ServerConfigWriter<'a, TestConfigWith<S>> {
+    ServerConfigWriter {
+        addr: ADDR,
+        config,
+        response,
+        keep_alive: true,
+        version: HttpVersion::Http11,
+        head: false,
+        cors_origin: None,
+        tx: None,
+    }
+}
+
+impl<S: TestSettings> Default for TestConfigWith<S> {
+    fn default() -> Self {
+        Self {
+            output: Vec::new(),
+            closed: false,
+            write_budget: None,
+            settings: PhantomData,
+        }
+    }
+}
+
+impl<S: TestSettings> ServerConfig for TestConfigWith<S> {
+    type ConnectionType = TestConnection;
+
+    const CORS: Option<CorsPolicy> = S::CORS;
+    const LIMITS: ServerLimits = S::LIMITS;
+    const TIMEOUTS: ServerTimeouts = S::TIMEOUTS;
+    const EVICTION: EvictionPolicy = S::EVICTION;
+
+    fn new_connection(
+        &self,
+        _addr: SocketAddr,
+        method: HttpMethod,
+        path: &str,
+        _version: HttpVersion,
+    ) -> Result<Self::ConnectionType> {
+        Ok(TestConnection {
+            method,
+            path: path.try_into().unwrap(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        })
+    }
+
+    fn on_client_done_writing(&mut self, _addr: SocketAddr, _client: &mut Self::ConnectionType) {}
+
+    fn write(&mut self, _addr: SocketAddr, bytes: &[u8]) -> WriteResult {
+        let len = self
+            .write_budget
+            .map_or(bytes.len(), |budget| budget.min(bytes.len()));
+        if len == 0 && !bytes.is_empty() {
+            return WriteResult::WouldBlock;
+        }
+        if let Some(budget) = &mut self.write_budget {
+            *budget -= len;
+        }
+        self.output.extend_from_slice(&bytes[..len]).unwrap();
+        WriteResult::Ok { bytes_written: len }
+    }
+
+    fn close_connection(&mut self, _addr: SocketAddr) {
+        self.closed = true;
+    }
+}
+
+/// Remembers the request and responds with the requested path.
+pub(crate) struct TestConnection {
+    pub(crate) method: HttpMethod,
+    pub(crate) path: String<64>,
+    pub(crate) headers: Vec<(String<32>, String<64>), 8>,
+    pub(crate) body: Vec<u8, 256>,
+}
+
+impl Connection for TestConnection {
+    fn header_received(&mut self, key: &str, value: &str) -> ReadResult {
+        self.headers
+            .push((key.try_into().unwrap(), value.try_into().unwrap()))
+            .unwrap();
+        ReadResult::Ok
+    }
+
+    fn switch_from_headers_to_body(&mut self) -> ReadResult {
+        ReadResult::Ok
+    }
+
+    fn body_received(&mut self, bytes: &[u8]) -> ReadResult {
+        self.body.extend_from_slice(bytes).unwrap();
+        ReadResult::Ok
+    }
+
+    fn poll_write(&mut self, writer: &mut dyn ResponseWriter) -> WriteResult {
+        writer.write_status(200, "OK")?;
+        writer.write_content_length(self.path.len() as u64)?;
+        writer.end_headers_start_body()?;
+        write!(writer, "{}", self.path)?;
+        WriteResult::EndWriting
+    }
+}
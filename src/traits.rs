@@ -1,4 +1,4 @@
-use crate::{Error, HttpMethod, Result};
+use crate::{CorsPolicy, Error, HeaderName, HttpDate, HttpMethod, HttpVersion, Result};
 use core::net::SocketAddr;
 
 pub trait ServerConfig {
@@ -9,30 +9,297 @@ pub trait ServerConfig {
         addr: SocketAddr,
         method: HttpMethod,
         path: &str,
+        version: HttpVersion,
     ) -> Result<Self::ConnectionType>;
 
+    /// The maximum amount of requests that are handled on a single keep-alive connection. After this many requests
+    /// the server responds with `Connection: close` and closes the connection.
+    const MAX_REQUESTS_PER_CONNECTION: usize = 100;
+
     fn on_client_done_writing(&mut self, addr: SocketAddr, client: &mut Self::ConnectionType);
+    /// Send `bytes` to `addr`.
+    ///
+    /// The transport doesn't have to take everything: return how many bytes it accepted, or `WriteResult::WouldBlock`
+    /// if it can't take any right now. The rest is kept in the TX buffer of the `Server` and sent by the next
+    /// `Server::poll_write`, so call that again once the transport has room.
     fn write(&mut self, addr: SocketAddr, bytes: &[u8]) -> WriteResult;
+    /// Called when the server is done with a client, the socket of `addr` should be closed.
+    fn close_connection(&mut self, addr: SocketAddr);
+
+    /// If `true`, the server responds to invalid requests with an error status like `400 Bad Request` before closing
+    /// the connection. Otherwise the connection is closed without a response.
+    ///
+    /// The error response is written with a single `write` right before the connection is closed, so it does not go
+    /// through the TX buffer of the `Server`. If the transport doesn't accept all of it, the response is truncated.
+    const SEND_ERROR_RESPONSES: bool = true;
+
+    /// The body of the error response that is sent for `error`. Defaults to the reason phrase of the status code.
+    fn error_response_body(&self, error: &Error) -> &'static str {
+        error.status().map_or("", |(_, reason)| reason)
+    }
+
+    /// Allow cross-origin requests from browsers, see `CorsPolicy`. Disabled by default.
+    const CORS: Option<CorsPolicy> = None;
+
+    /// Limits on the size of requests, requests that exceed them are rejected with an error response.
+    const LIMITS: ServerLimits = ServerLimits::DEFAULT;
+
+    /// Timeouts that are enforced by `Server::tick`.
+    const TIMEOUTS: ServerTimeouts = ServerTimeouts::DEFAULT;
+
+    /// What `Server::new_connection` does when all connections are in use.
+    const EVICTION: EvictionPolicy = EvictionPolicy::Reject;
+
+    /// Called when the connection of `addr` is closed to make room for a new connection, see
+    /// `ServerConfig::EVICTION`. Defaults to `close_connection`.
+    fn connection_evicted(&mut self, addr: SocketAddr) {
+        self.close_connection(addr);
+    }
+}
+
+/// Which connection, if any, is closed when a new connection comes in while all connections are in use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Don't close any connection, the new connection gets `503 Service Unavailable`.
+    Reject,
+    /// Close the keep-alive connection that has been waiting for its next request the longest.
+    OldestIdle,
+    /// Close the connection that has been waiting for (the rest of) its request line the longest. Unlike
+    /// `OldestIdle`, this includes connections that never sent a request.
+    OldestReadingRequestLine,
+}
+
+/// Limits on the size of requests, see `ServerConfig::LIMITS`.
+///
+/// ```ignore
+/// const LIMITS: ServerLimits = ServerLimits {
+///     max_body_size: Some(4096),
+///     ..ServerLimits::DEFAULT
+/// };
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ServerLimits {
+    /// The longest request line, longer lines get `414 URI Too Long`.
+    ///
+    /// This limit and `max_header_length` are capped at the `BUFFER_SIZE` of the `Server`, which holds lines that are
+    /// split over multiple `data_received` calls.
+    pub max_request_line_length: usize,
+    /// The longest header line, longer lines get `431 Request Header Fields Too Large`. This also limits the trailer
+    /// lines and the chunk size lines of a chunked body.
+    pub max_header_length: usize,
+    /// The maximum amount of headers in a request, more headers get `431 Request Header Fields Too Large`. The trailer
+    /// fields of a chunked body count towards this.
+    pub max_headers: usize,
+    /// The largest request body, larger bodies get `413 Content Too Large`. `None` allows bodies of any size.
+    ///
+    /// This can be changed for a single request with `Connection::max_body_size`.
+    pub max_body_size: Option<u64>,
+}
+
+impl ServerLimits {
+    pub const DEFAULT: Self = Self {
+        max_request_line_length: 2048,
+        max_header_length: 2048,
+        max_headers: 64,
+        max_body_size: None,
+    };
+}
+
+impl Default for ServerLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Timeouts for clients that stop sending or receiving, see `ServerConfig::TIMEOUTS`.
+///
+/// The timeouts use the same unit as the clock that is passed to `Server::tick`, the defaults assume milliseconds.
+/// `None` disables a timeout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ServerTimeouts {
+    /// The time a client has to send the request line and headers, from the first byte of the request. The client
+    /// gets `408 Request Timeout` (see `ServerConfig::SEND_ERROR_RESPONSES`).
+    pub header_read: Option<u64>,
+    /// The time the body of a request can be silent before the client gets `408 Request Timeout`.
+    pub body_read: Option<u64>,
+    /// The time a connection can stay open without sending a request, both for new and keep-alive connections.
+    pub idle: Option<u64>,
+    /// The time the response can be stuck, without any data being written, before the connection is closed.
+    pub write: Option<u64>,
+}
+
+impl ServerTimeouts {
+    pub const DEFAULT: Self = Self {
+        header_read: Some(10_000),
+        body_read: Some(10_000),
+        idle: Some(30_000),
+        write: Some(10_000),
+    };
+
+    /// No timeouts, connections are only closed by the client.
+    pub const NONE: Self = Self {
+        header_read: None,
+        body_read: None,
+        idle: None,
+        write: None,
+    };
+}
+
+impl Default for ServerTimeouts {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Write a complete error response to `addr`. This is used for requests that never reach a `Connection`.
+pub(crate) fn write_error_response<S: ServerConfig>(
+    config: &mut S,
+    addr: SocketAddr,
+    version: HttpVersion,
+    code: u16,
+    reason: &str,
+    error: &Error,
+) -> WriteResult {
+    let body = config.error_response_body(error);
+    let mut response = ResponseState::default();
+    let mut writer = ServerConfigWriter {
+        addr,
+        config,
+        response: &mut response,
+        keep_alive: false,
+        version,
+        head: false,
+        cors_origin: None,
+        tx: None,
+    };
+    writer.write_status(code, reason)?;
+    writer.write_response_header("Content-Type", "text/plain")?;
+    writer.write_content_length(body.len() as u64)?;
+    writer.end_headers_start_body()?;
+    write!(writer, "{body}")
 }
 
 pub(crate) struct ServerConfigWriter<'a, S: ServerConfig> {
     pub(crate) addr: SocketAddr,
     pub(crate) config: &'a mut S,
+    pub(crate) response: &'a mut ResponseState,
+    /// `true` if the request allows the connection to be kept open after this response.
+    pub(crate) keep_alive: bool,
+    /// The version of the request, the response is sent with the same version.
+    pub(crate) version: HttpVersion,
+    /// `true` if the request was a HEAD request, everything after the headers is dropped.
+    pub(crate) head: bool,
+    /// The `Access-Control-Allow-Origin` of the response, if the request came from an origin allowed by
+    /// `ServerConfig::CORS`.
+    pub(crate) cors_origin: Option<&'static str>,
+    /// Holds what the transport doesn't accept, see `ServerConfigWriter::send`. Without it, the result of
+    /// `ServerConfig::write` is returned as-is.
+    pub(crate) tx: Option<&'a mut dyn TxBuffer>,
 }
 
-impl<'a, S: ServerConfig> ResponseWriter for ServerConfigWriter<'a, S> {
-    fn write_fmt(&mut self, fmt: core::fmt::Arguments) -> WriteResult {
-        struct FmtWriter<'a, S: ServerConfig> {
-            addr: SocketAddr,
-            config: &'a mut S,
+/// The bytes of a response that the transport has not accepted yet, see `Server::poll_write`.
+pub(crate) trait TxBuffer {
+    fn is_empty(&self) -> bool;
+    /// The amount of bytes that can still be appended.
+    fn free(&self) -> usize;
+    fn append(&mut self, bytes: &[u8]) -> Result;
+}
+
+impl<const N: usize> TxBuffer for heapless::Vec<u8, N> {
+    fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+
+    fn free(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> Result {
+        self.extend_from_slice(bytes)
+            .map_err(|()| Error::TxBufferFull)
+    }
+}
+
+/// The longest chunk size line, plus the line break after the data of a chunk.
+const CHUNK_FRAMING: usize = 2 * core::mem::size_of::<usize>() + 4;
+
+/// State of the response that has to be remembered between calls to `Connection::poll_write`.
+#[derive(Default)]
+pub(crate) struct ResponseState {
+    /// Set by `ResponseWriter::end_headers_start_chunked_body`, every write after this is sent as a single chunk.
+    pub(crate) chunked: bool,
+    /// `true` if the client can tell where the body ends without the connection being closed.
+    pub(crate) framed: bool,
+    /// `true` if the connection has to be closed after this response.
+    pub(crate) close: bool,
+    /// `true` once the headers are written.
+    pub(crate) body_started: bool,
+    /// `true` once the connection returned `WriteResult::EndWriting`. The response is done when the TX buffer is
+    /// empty.
+    pub(crate) ended: bool,
+    /// `true` once part of the response did not fit in the TX buffer. Nothing is sent after that, the connection is
+    /// closed instead.
+    pub(crate) lost: bool,
+}
+
+/// The length of formatted text, without writing it anywhere.
+pub(crate) fn formatted_length(fmt: core::fmt::Arguments) -> usize {
+    struct LengthCounter(usize);
+
+    impl core::fmt::Write for LengthCounter {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    let mut counter = LengthCounter(0);
+    let _ = core::fmt::write(&mut counter, fmt);
+    counter.0
+}
+
+impl<'a, S: ServerConfig> ServerConfigWriter<'a, S> {
+    /// Send `bytes` to the client. Whatever the transport doesn't accept is appended to the TX buffer, so the
+    /// connection never has to write it again. Fails with `Error::TxBufferFull` if it doesn't fit.
+    fn send(&mut self, bytes: &[u8]) -> WriteResult {
+        if self.response.lost {
+            return WriteResult::Err(Error::TxBufferFull);
+        }
+        let Some(tx) = self.tx.as_deref_mut() else {
+            return self.config.write(self.addr, bytes);
+        };
+        // once something is buffered, everything after it has to wait as well to keep the order
+        let written = if tx.is_empty() {
+            match self.config.write(self.addr, bytes) {
+                WriteResult::Ok { bytes_written } => bytes_written.min(bytes.len()),
+                WriteResult::WouldBlock => 0,
+                result => return result,
+            }
+        } else {
+            0
+        };
+        if let Err(e) = tx.append(&bytes[written..]) {
+            // the client would get a response with a gap in it
+            self.response.lost = true;
+            return WriteResult::Err(e);
+        }
+        WriteResult::Ok {
+            bytes_written: bytes.len(),
+        }
+    }
+
+    /// Write `fmt` to the client as-is.
+    fn write_raw(&mut self, fmt: core::fmt::Arguments) -> WriteResult {
+        struct FmtWriter<'w, 'a, S: ServerConfig> {
+            writer: &'w mut ServerConfigWriter<'a, S>,
             response: Option<WriteResult>,
         }
 
-        impl<'a, S: ServerConfig> core::fmt::Write for FmtWriter<'a, S> {
+        impl<'w, 'a, S: ServerConfig> core::fmt::Write for FmtWriter<'w, 'a, S> {
             fn write_str(&mut self, s: &str) -> core::fmt::Result {
-                let res = self.config.write(self.addr, s.as_bytes());
+                let res = self.writer.send(s.as_bytes());
                 // only overwrite the response if the previous wasn't an error
-                if !self.response.as_ref().map_or(false, |r| r.is_err()) {
+                if !self.response.as_ref().is_some_and(|r| r.is_err()) {
                     self.response = Some(res);
                 }
 
@@ -45,8 +312,7 @@ impl<'a, S: ServerConfig> ResponseWriter for ServerConfigWriter<'a, S> {
             }
         }
         let mut writer = FmtWriter {
-            addr: self.addr,
-            config: self.config,
+            writer: self,
             response: None,
         };
         // we don't care about this error because we're going to use `response` instead
@@ -55,12 +321,174 @@ impl<'a, S: ServerConfig> ResponseWriter for ServerConfigWriter<'a, S> {
             .response
             .unwrap_or(WriteResult::Ok { bytes_written: 0 })
     }
+
+    /// Called when the connection returns `WriteResult::EndWriting`. Writes the last chunk of a chunked response.
+    pub(crate) fn finish(&mut self) -> WriteResult {
+        if core::mem::take(&mut self.response.chunked) {
+            self.write_raw(format_args!("0\r\n\r\n"))?;
+        }
+        WriteResult::EndWriting
+    }
+}
+
+impl<'a, S: ServerConfig> ResponseWriter for ServerConfigWriter<'a, S> {
+    fn write_fmt(&mut self, fmt: core::fmt::Arguments) -> WriteResult {
+        let discard_body = self.head && self.response.body_started;
+        if !self.response.chunked && !discard_body {
+            return self.write_raw(fmt);
+        }
+
+        // The chunk size has to be sent before the data, so format everything once to find out how long it is
+        let length = formatted_length(fmt);
+        if discard_body {
+            return WriteResult::Ok {
+                bytes_written: length,
+            };
+        }
+        // an empty chunk would end the body
+        if length == 0 {
+            return WriteResult::Ok { bytes_written: 0 };
+        }
+
+        self.write_raw(format_args!("{length:x}\r\n"))?;
+        self.write_raw(fmt)?;
+        self.write_raw(format_args!("\r\n"))?;
+        WriteResult::Ok {
+            bytes_written: length,
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> WriteResult {
+        if self.head && self.response.body_started {
+            return WriteResult::Ok {
+                bytes_written: bytes.len(),
+            };
+        }
+        if !self.response.chunked {
+            return self.send(bytes);
+        }
+        // an empty chunk would end the body
+        if bytes.is_empty() {
+            return WriteResult::Ok { bytes_written: 0 };
+        }
+        self.write_raw(format_args!("{:x}\r\n", bytes.len()))?;
+        let bytes_written = self.send(bytes)?;
+        self.write_raw(format_args!("\r\n"))?;
+        WriteResult::Ok { bytes_written }
+    }
+
+    fn write_capacity(&self) -> usize {
+        let free = self.tx.as_ref().map_or(usize::MAX, |tx| tx.free());
+        if self.response.chunked {
+            free.saturating_sub(CHUNK_FRAMING)
+        } else {
+            free
+        }
+    }
+
+    fn write_status(&mut self, code: u16, value: &str) -> WriteResult {
+        // These responses never have a body
+        if matches!(code, 100..=199 | 204 | 304) {
+            self.response.framed = true;
+        }
+        write!(self, "{} {code} {value}\r\n", self.version.as_str())?;
+        if let Some(policy) = &S::CORS {
+            let origin = self.cors_origin;
+            policy.write_headers(self, origin)?;
+        }
+        WriteResult::Ok { bytes_written: 0 }
+    }
+
+    fn write_response_header(&mut self, key: &str, value: &str) -> WriteResult {
+        match HeaderName::from(key) {
+            HeaderName::ContentLength => self.response.framed = true,
+            HeaderName::Connection if value.eq_ignore_ascii_case("close") => {
+                self.response.close = true
+            }
+            _ => {}
+        }
+        write!(self, "{key}: {value}\r\n")
+    }
+
+    fn write_content_length(&mut self, length: u64) -> WriteResult {
+        self.response.framed = true;
+        write!(self, "Content-Length: {length}\r\n")
+    }
+
+    fn end_headers_start_body(&mut self) -> WriteResult {
+        // Without a length the body can only be ended by closing the connection. A HEAD response never has a body.
+        if !self.response.framed && !self.head {
+            self.response.close = true;
+        }
+        if !self.keep_alive && !self.response.close {
+            self.write_response_header("Connection", "close")?;
+        } else if self.keep_alive && !self.response.close && self.version == HttpVersion::Http10 {
+            // HTTP/1.0 closes the connection unless told otherwise
+            self.write_response_header("Connection", "keep-alive")?;
+        }
+        write!(self, "\r\n")?;
+        self.response.body_started = true;
+        WriteResult::Ok { bytes_written: 0 }
+    }
+
+    fn end_headers_start_chunked_body(&mut self) -> WriteResult {
+        // HTTP/1.0 does not support chunked bodies, the end of the body is marked by closing the connection instead
+        if self.version == HttpVersion::Http10 {
+            return self.end_headers_start_body();
+        }
+        self.response.framed = true;
+        self.write_response_header("Transfer-Encoding", "chunked")?;
+        self.end_headers_start_body()?;
+        // there are no chunks in the (empty) body of a HEAD response
+        self.response.chunked = !self.head;
+        WriteResult::Ok { bytes_written: 0 }
+    }
+}
+
+#[test]
+fn test_chunked_response() {
+    use crate::test_utilities::{writer, TestConfig};
+
+    let mut config = TestConfig::default();
+    let mut response = ResponseState::default();
+    let mut writer = writer(&mut config, &mut response);
+    writer.write_status(200, "OK");
+    writer.end_headers_start_chunked_body();
+    let name = "world";
+    write!(writer, "hello {name}");
+    write!(writer, "");
+    write!(writer, "{}", 42);
+    assert!(matches!(writer.finish(), WriteResult::EndWriting));
+    assert_eq!(
+        core::str::from_utf8(&config.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nb\r\nhello world\r\n2\r\n42\r\n0\r\n\r\n"
+    );
 }
 
 pub trait Connection {
     fn header_received(&mut self, key: &str, value: &str) -> ReadResult;
+    /// Called after the last header. Returning `ReadResult::EndReading` skips the body entirely.
     fn switch_from_headers_to_body(&mut self) -> ReadResult;
+    /// Called with the next part of the request body.
+    ///
+    /// The server keeps track of the `Content-Length` header and will stop reading by itself once the entire body is
+    /// received. Bodies with `Transfer-Encoding: chunked` are decoded, so this only receives the payload.
+    /// This is not called for requests without a body.
     fn body_received(&mut self, bytes: &[u8]) -> ReadResult;
+    /// The largest body that this request may have, which overrides `ServerLimits::max_body_size`. Called after the
+    /// last header.
+    ///
+    /// Use this to allow larger bodies for specific requests, like a firmware upload.
+    fn max_body_size(&self) -> Option<u64> {
+        None
+    }
+    /// Write (the next part of) the response. Return `WriteResult::EndWriting` when it is complete, or
+    /// `WriteResult::Ok` to be called again.
+    ///
+    /// A single call should not write more than `ResponseWriter::write_capacity` bytes. What the transport doesn't
+    /// accept is kept in the TX buffer of the `Server`, and a response that doesn't fit is ended by closing the
+    /// connection. At the start of a call the capacity is the full `TX_BUFFER_SIZE`, so the headers and a small body
+    /// can be written at once. Write large bodies in slices over multiple calls.
     fn poll_write(&mut self, writer: &mut dyn ResponseWriter) -> WriteResult;
 }
 
@@ -71,9 +499,13 @@ pub enum ReadResult {
 }
 
 pub enum WriteResult {
-    Ok { bytes_written: usize },
+    Ok {
+        bytes_written: usize,
+    },
     Err(Error),
     EndWriting,
+    /// Returned by `ServerConfig::write` when the transport can't accept any bytes right now.
+    WouldBlock,
 }
 
 impl WriteResult {
@@ -90,16 +522,60 @@ impl core::ops::FromResidual for WriteResult {
 
 pub trait ResponseWriter {
     fn write_fmt(&mut self, fmt: core::fmt::Arguments) -> WriteResult;
+    /// Write binary data, e.g. an image. Use `write!` for text.
+    fn write_bytes(&mut self, bytes: &[u8]) -> WriteResult;
+
+    /// The amount of bytes that can be written in this `Connection::poll_write` without failing with
+    /// `Error::TxBufferFull`, should the transport stop accepting data.
+    ///
+    /// Write large bodies in slices of at most this size, and the rest in the next `poll_write`.
+    fn write_capacity(&self) -> usize {
+        usize::MAX
+    }
 
     fn write_status(&mut self, code: u16, value: &str) -> WriteResult {
-        writeln!(self, "HTTP/1.1 {code} {value}")
+        write!(self, "HTTP/1.1 {code} {value}\r\n")
     }
 
     fn write_response_header(&mut self, key: &str, value: &str) -> WriteResult {
-        writeln!(self, "{key}: {value}")
+        write!(self, "{key}: {value}\r\n")
+    }
+    fn write_content_length(&mut self, length: u64) -> WriteResult {
+        write!(self, "Content-Length: {length}\r\n")
     }
     fn end_headers_start_body(&mut self) -> WriteResult {
-        writeln!(self)
+        write!(self, "\r\n")
+    }
+
+    /// Ends the headers like `end_headers_start_body`, and sends the body with `Transfer-Encoding: chunked`.
+    ///
+    /// Every following `write_fmt` call is sent as a single chunk, and the body is terminated when the connection
+    /// returns `WriteResult::EndWriting`. Use this when the length of the body is not known up front.
+    ///
+    /// HTTP/1.0 clients don't support this, for them the body is sent as-is and the connection is closed afterwards.
+    fn end_headers_start_chunked_body(&mut self) -> WriteResult;
+
+    /// Write a complete `304 Not Modified` response, for when `ConditionalHeaders::is_not_modified` returns `true`.
+    ///
+    /// The `Vary` header and validators of the response are repeated, as required by RFC 9110 section 15.4.5. Return
+    /// `WriteResult::EndWriting` after this, a `304` response has no body.
+    fn write_not_modified(
+        &mut self,
+        vary: Option<&str>,
+        etag: Option<&str>,
+        last_modified: Option<HttpDate>,
+    ) -> WriteResult {
+        self.write_status(304, "Not Modified")?;
+        if let Some(vary) = vary {
+            self.write_response_header("Vary", vary)?;
+        }
+        if let Some(etag) = etag {
+            self.write_response_header("ETag", etag)?;
+        }
+        if let Some(last_modified) = last_modified {
+            write!(self, "Last-Modified: {last_modified}\r\n")?;
+        }
+        self.end_headers_start_body()
     }
 }
 